@@ -1,8 +1,8 @@
-use reqwest::Body;
 use somfy_sdk::api_client::ApiClient;
 use somfy_sdk::commands::execute_action_group::ExecuteActionGroupResponse;
-use somfy_sdk::commands::traits::{HttpMethod, RequestData, SomfyApiRequestCommand};
+use somfy_sdk::commands::traits::{Body, HttpMethod, RequestData, SomfyApiRequestCommand};
 use somfy_sdk::commands::types::{Action, ActionGroup, Command};
+use somfy_sdk::device_url::DeviceUrl;
 use somfy_sdk::err::http::RequestError;
 use std::collections::HashMap;
 
@@ -22,19 +22,21 @@ impl SomfyApiRequestCommand for CloseLivingRoomShuttersCommand {
 
         // Validate position at runtime (or use newtypes for compile-time validation)
         let position = self.position.min(100);
+        let device_url =
+            |url: &str| DeviceUrl::parse(url).map_err(|e| RequestError::Server(e.into()));
 
         let action_group = ActionGroup {
             label: Some("Close living room shutters".to_string()),
             actions: vec![
                 Action {
-                    device_url: LIVING_ROOM_SHUTTER_EAST_URL.to_string(),
+                    device_url: device_url(LIVING_ROOM_SHUTTER_EAST_URL)?,
                     commands: vec![Command {
                         name: "setClosure".to_string(),
                         parameters: vec![position.to_string()],
                     }],
                 },
                 Action {
-                    device_url: LIVING_ROOM_SHUTTER_SOUTH_URL.to_string(),
+                    device_url: device_url(LIVING_ROOM_SHUTTER_SOUTH_URL)?,
                     commands: vec![Command {
                         name: "setClosure".to_string(),
                         parameters: vec![position.to_string()],
@@ -1,3 +1,4 @@
+use crate::cassette::{Cassette, ReplayTransport};
 use crate::commands::cancel_all_executions::{
     CancelAllExecutionsCommand, CancelAllExecutionsResponse,
 };
@@ -21,173 +22,187 @@ use crate::commands::get_version::{GetVersionCommand, GetVersionResponse};
 use crate::commands::register_event_listener::{
     RegisterEventListenerCommand, RegisterEventListenerResponse,
 };
+use crate::commands::traits::RequestData;
+use crate::commands::traits::SomfyApiRequestCommand;
 use crate::commands::traits::SomfyApiRequestResponse;
-use crate::commands::traits::{HttpMethod, RequestData, SomfyApiRequestCommand};
 use crate::commands::unregister_event_listener::{
     UnregisterEventListenerCommand, UnregisterEventListenerResponse,
 };
-use crate::config::tls_cert::TlsCertHandler;
+use crate::device_url::DeviceUrl;
 use crate::err::http::RequestError;
-use log::debug;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
-use reqwest::{Certificate, Client, ClientBuilder, Response};
+use crate::transport::{RawResponse, Transport};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+#[cfg(feature = "client")]
+use {
+    crate::cassette::RecordingTransport,
+    crate::commands::traits::HttpMethod,
+    crate::config::pinning::pinned_tls_config,
+    crate::config::tls_cert::TlsCertHandler,
+    crate::err::cert::CertificateError,
+    crate::err::config::ConfigError,
+    crate::transport::TransportFuture,
+    log::debug,
+    reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    reqwest::{Certificate, Client, ClientBuilder, Response},
+    std::net::{IpAddr, Ipv6Addr, SocketAddr},
+    std::path::Path,
+};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum HttpProtocol {
     HTTP,
     HTTPS,
 }
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", from = "CertificateHandlingRepr")]
 pub enum CertificateHandling {
     CertProvided(String),
+    // Optional cache directory, see TlsCertHandler::get_folder_location for the fallbacks
+    DefaultCert(Option<String>),
+    // The Overkiz root CA built in with the `embedded-cert` feature, `NoCertAvailable` without
+    Embedded,
+    // Hex encoded SHA-256 of the gateway certificate's SubjectPublicKeyInfo
+    Pinned(String),
+    NoCustomCert,
+}
+
+// Allows `"default_cert"` in config files as shorthand for `{ default_cert = <none> }`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CertificateHandlingRepr {
+    Short(ShortCertificateHandling),
+    Full(FullCertificateHandling),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ShortCertificateHandling {
     DefaultCert,
+    Embedded,
     NoCustomCert,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FullCertificateHandling {
+    CertProvided(String),
+    DefaultCert(Option<String>),
+    Pinned(String),
+}
+
+impl From<CertificateHandlingRepr> for CertificateHandling {
+    fn from(value: CertificateHandlingRepr) -> Self {
+        match value {
+            CertificateHandlingRepr::Short(ShortCertificateHandling::DefaultCert) => {
+                CertificateHandling::DefaultCert(None)
+            }
+            CertificateHandlingRepr::Short(ShortCertificateHandling::Embedded) => {
+                CertificateHandling::Embedded
+            }
+            CertificateHandlingRepr::Short(ShortCertificateHandling::NoCustomCert) => {
+                CertificateHandling::NoCustomCert
+            }
+            CertificateHandlingRepr::Full(FullCertificateHandling::CertProvided(path)) => {
+                CertificateHandling::CertProvided(path)
+            }
+            CertificateHandlingRepr::Full(FullCertificateHandling::DefaultCert(cache_dir)) => {
+                CertificateHandling::DefaultCert(cache_dir)
+            }
+            CertificateHandlingRepr::Full(FullCertificateHandling::Pinned(pin)) => {
+                CertificateHandling::Pinned(pin)
+            }
+        }
+    }
+}
+
+// The API key is never serialized nor printed, so configs can be saved and logged
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct ApiClientConfig {
     pub cert_handling: CertificateHandling,
     pub protocol: HttpProtocol,
     pub url: String,
-    pub port: usize,
+    pub port: u16,
+    #[serde(skip_serializing)]
     pub api_key: String,
+    // Host name to verify the certificate against when `url` is an IP address,
+    // e.g. `gateway-0000-1111-2222.local`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_server_name: Option<String>,
+}
+
+impl std::fmt::Debug for ApiClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiClientConfig")
+            .field("cert_handling", &self.cert_handling)
+            .field("protocol", &self.protocol)
+            .field("url", &self.url)
+            .field("port", &self.port)
+            .field("api_key", &"[REDACTED]")
+            .field("tls_server_name", &self.tls_server_name)
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ApiClient {
-    config: ApiClientConfig,
-    http_client: Client,
+    transport: Arc<dyn Transport>,
 }
 
-const DEFAULT_PORT: usize = 8443;
+pub(crate) const DEFAULT_PORT: u16 = 8443;
 
 impl ApiClient {
+    #[cfg(feature = "client")]
     pub async fn new(config: ApiClientConfig) -> Result<Self, RequestError> {
-        debug!("Initialized ApiClient with Config: {config:?}");
-        let http_client = Self::build_client(&config).await?;
-        Ok(Self {
-            config,
-            http_client,
-        })
+        Ok(Self::with_transport(ReqwestTransport::new(config).await?))
     }
 
-    pub async fn from(id: &str, api_key: &str) -> Result<Self, RequestError> {
-        let config = ApiClientConfig {
-            url: format!("gateway-{id}.local"),
-            port: DEFAULT_PORT,
-            api_key: api_key.to_string(),
-            protocol: HttpProtocol::HTTPS,
-            cert_handling: CertificateHandling::DefaultCert,
-        };
-        Self::new(config).await
-    }
-
-    async fn build_client(config: &ApiClientConfig) -> Result<Client, RequestError> {
-        let headers = Self::generate_default_headers(config)?;
-        let mut client = ClientBuilder::new().default_headers(headers);
-
-        if let Some(certificate) = Self::ensure_cert(config).await? {
-            client = client.add_root_certificate(certificate)
+    /// A client sending its requests through `transport`, e.g. a `MockTransport` in tests.
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(transport),
         }
-
-        Ok(client.build()?)
-    }
-
-    async fn make_post_request(
-        &self,
-        request_data: RequestData,
-    ) -> Result<Response, reqwest::Error> {
-        let content_len = &request_data.get_content_length();
-        let path = self.generate_base_url(&request_data);
-
-        self.http_client
-            .post(&path)
-            .body(request_data.body)
-            .header("content-length", content_len)
-            .header("content-type", "application/json")
-            .send()
-            .await?
-            .error_for_status()
     }
 
-    async fn make_get_request(
-        &self,
-        request_data: RequestData,
-    ) -> Result<Response, reqwest::Error> {
-        let path = self.generate_base_url(&request_data);
-        self.http_client.get(&path).send().await?.error_for_status()
+    /// A client that sends requests to the gateway and records them, together with the
+    /// responses, to a cassette at `path`. The API key is redacted from the recording.
+    #[cfg(feature = "client")]
+    pub async fn recording(
+        config: ApiClientConfig,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, RequestError> {
+        let api_key = config.api_key.clone();
+        let transport = ReqwestTransport::new(config).await?;
+        let recording = RecordingTransport::new(transport, path, &api_key)
+            .map_err(|e| RequestError::Server(e.into()))?;
+        Ok(Self::with_transport(recording))
     }
 
-    async fn make_delete_request(
-        &self,
-        request_data: RequestData,
-    ) -> Result<Response, reqwest::Error> {
-        let path = self.generate_base_url(&request_data);
-        self.http_client
-            .delete(&path)
-            .send()
-            .await?
-            .error_for_status()
+    /// A client answering requests from `cassette` without connecting to a gateway. Requests
+    /// are matched on method, path, query and body, requests without a recorded response fail.
+    pub fn replaying(cassette: Cassette) -> Self {
+        Self::with_transport(ReplayTransport::new(cassette))
     }
 
-    async fn make_api_request(
-        &self,
-        request_data: RequestData,
-    ) -> Result<Response, reqwest::Error> {
-        match request_data.method {
-            HttpMethod::GET => self.make_get_request(request_data).await,
-            HttpMethod::POST => self.make_post_request(request_data).await,
-            HttpMethod::DELETE => self.make_delete_request(request_data).await,
-        }
+    #[cfg(feature = "client")]
+    pub async fn from(id: &str, api_key: &str) -> Result<Self, RequestError> {
+        Self::new(gateway_config(id, api_key)).await
     }
 
     pub async fn execute<C>(&self, command: C) -> Result<C::Response, RequestError>
     where
         C: SomfyApiRequestCommand,
     {
-        let request_data = command.to_request()?;
-        let response = self.make_api_request(request_data).await?;
-
-        let body = response.text().await?;
-        C::Response::from_body(body.as_str())
+        let response = self.send(command.to_request()?).await?;
+        C::Response::from_response(response.status, &response.body)
     }
 
-    async fn ensure_cert(config: &ApiClientConfig) -> Result<Option<Certificate>, RequestError> {
-        Ok(match &config.cert_handling {
-            CertificateHandling::CertProvided(path) => {
-                let crt = std::fs::read(path).map_err(|_| RequestError::Cert)?;
-                Some(Certificate::from_pem(&crt)?)
-            }
-            CertificateHandling::DefaultCert => {
-                let cert = TlsCertHandler::ensure_local_certificate()
-                    .await
-                    .map_err(|_| RequestError::Cert)?;
-
-                Some(cert)
-            }
-            CertificateHandling::NoCustomCert => None,
-        })
-    }
-
-    fn generate_base_url(&self, request_data: &RequestData) -> String {
-        let protocol = match self.config.protocol {
-            HttpProtocol::HTTP => "http",
-            HttpProtocol::HTTPS => "https",
-        };
-
-        let path = format!(
-            "{}://{}:{}{}",
-            protocol, self.config.url, self.config.port, request_data.path
-        );
-        path
-    }
-
-    fn generate_default_headers(config: &ApiClientConfig) -> Result<HeaderMap, RequestError> {
-        let mut headers = HeaderMap::new();
-        let bearer_token = HeaderValue::from_str(format!("Bearer {}", config.api_key).as_str())
-            .map_err(|e| RequestError::Server(e.into()))?;
-        headers.insert(AUTHORIZATION, bearer_token);
-        Ok(headers)
+    pub(crate) async fn send(
+        &self,
+        request_data: RequestData,
+    ) -> Result<RawResponse, RequestError> {
+        self.transport.send(request_data).await
     }
 
     pub async fn get_version(&self) -> Result<GetVersionResponse, RequestError> {
@@ -202,7 +217,10 @@ impl ApiClient {
         self.execute(GetDevicesCommand).await
     }
 
-    pub async fn get_device(&self, device_url: &str) -> Result<GetDeviceResponse, RequestError> {
+    pub async fn get_device(
+        &self,
+        device_url: &DeviceUrl,
+    ) -> Result<GetDeviceResponse, RequestError> {
         self.execute(GetDeviceCommand { device_url }).await
     }
 
@@ -212,14 +230,14 @@ impl ApiClient {
 
     pub async fn get_device_states(
         &self,
-        device_url: &str,
+        device_url: &DeviceUrl,
     ) -> Result<GetDeviceStatesResponse, RequestError> {
         self.execute(GetDeviceStatesCommand { device_url }).await
     }
 
     pub async fn get_device_state(
         &self,
-        device_url: &str,
+        device_url: &DeviceUrl,
         state_name: &str,
     ) -> Result<GetDeviceStateResponse, RequestError> {
         self.execute(GetDeviceStateCommand {
@@ -295,11 +313,179 @@ impl ApiClient {
     }
 }
 
-#[cfg(test)]
+// Config for `ApiClient::from`
+#[cfg(feature = "client")]
+fn gateway_config(id: &str, api_key: &str) -> ApiClientConfig {
+    ApiClientConfig {
+        url: format!("gateway-{id}.local"),
+        port: DEFAULT_PORT,
+        api_key: api_key.to_string(),
+        protocol: HttpProtocol::HTTPS,
+        cert_handling: CertificateHandling::DefaultCert(None),
+        tls_server_name: None,
+    }
+}
+
+/// Sends requests to a gateway over HTTP(S), used by `ApiClient::new`.
+#[cfg(feature = "client")]
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    config: ApiClientConfig,
+    http_client: Client,
+}
+
+#[cfg(feature = "client")]
+impl ReqwestTransport {
+    pub async fn new(config: ApiClientConfig) -> Result<Self, RequestError> {
+        debug!("Initialized ApiClient with Config: {config:?}");
+        let http_client = Self::build_client(&config).await?;
+        Ok(Self {
+            config,
+            http_client,
+        })
+    }
+
+    async fn build_client(config: &ApiClientConfig) -> Result<Client, RequestError> {
+        let headers = Self::generate_default_headers(config)?;
+        let mut client = ClientBuilder::new().default_headers(headers);
+
+        // Connect to the configured IP while using the server name for SNI and verification
+        if let Some(server_name) = &config.tls_server_name {
+            let ip = config.url.parse::<IpAddr>().map_err(|_| {
+                RequestError::Server(
+                    ConfigError::InvalidHost {
+                        host: config.url.clone(),
+                        reason: "must be an IP address when tls_server_name is set",
+                    }
+                    .into(),
+                )
+            })?;
+            client = client.resolve(server_name, SocketAddr::new(ip, config.port));
+        }
+
+        if let CertificateHandling::Pinned(pin) = &config.cert_handling {
+            let tls = pinned_tls_config(pin)?;
+            client = client.use_preconfigured_tls(tls);
+        } else if let Some(certificate) = Self::ensure_cert(config).await? {
+            client = client.add_root_certificate(certificate)
+        }
+
+        Ok(client.build()?)
+    }
+
+    async fn make_post_request(
+        &self,
+        request_data: RequestData,
+    ) -> Result<Response, reqwest::Error> {
+        let content_len = &request_data.get_content_length();
+        let path = self.generate_base_url(&request_data);
+
+        self.http_client
+            .post(&path)
+            .body(request_data.body.into_bytes())
+            .header("content-length", content_len)
+            .header("content-type", "application/json")
+            .send()
+            .await
+    }
+
+    async fn make_get_request(
+        &self,
+        request_data: RequestData,
+    ) -> Result<Response, reqwest::Error> {
+        let path = self.generate_base_url(&request_data);
+        self.http_client.get(&path).send().await
+    }
+
+    async fn make_delete_request(
+        &self,
+        request_data: RequestData,
+    ) -> Result<Response, reqwest::Error> {
+        let path = self.generate_base_url(&request_data);
+        self.http_client.delete(&path).send().await
+    }
+
+    async fn make_api_request(
+        &self,
+        request_data: RequestData,
+    ) -> Result<Response, reqwest::Error> {
+        match request_data.method {
+            HttpMethod::GET => self.make_get_request(request_data).await,
+            HttpMethod::POST => self.make_post_request(request_data).await,
+            HttpMethod::DELETE => self.make_delete_request(request_data).await,
+        }
+    }
+
+    async fn ensure_cert(config: &ApiClientConfig) -> Result<Option<Certificate>, RequestError> {
+        Ok(match &config.cert_handling {
+            CertificateHandling::CertProvided(path) => {
+                let crt = std::fs::read(path).map_err(|source| CertificateError::Unreadable {
+                    path: path.into(),
+                    source,
+                })?;
+                Some(Certificate::from_pem(&crt).map_err(|_| CertificateError::InvalidLocalCert)?)
+            }
+            CertificateHandling::DefaultCert(cache_dir) => {
+                let cert =
+                    TlsCertHandler::ensure_local_certificate(cache_dir.as_deref().map(Path::new))
+                        .await?;
+
+                Some(cert)
+            }
+            CertificateHandling::Embedded => Some(TlsCertHandler::embedded_certificate()?),
+            CertificateHandling::Pinned(_) | CertificateHandling::NoCustomCert => None,
+        })
+    }
+
+    fn generate_base_url(&self, request_data: &RequestData) -> String {
+        let protocol = match self.config.protocol {
+            HttpProtocol::HTTP => "http",
+            HttpProtocol::HTTPS => "https",
+        };
+
+        let host = match self.config.tls_server_name.as_deref() {
+            Some(server_name) => server_name.to_string(),
+            None if self.config.url.parse::<Ipv6Addr>().is_ok() => format!("[{}]", self.config.url),
+            None => self.config.url.clone(),
+        };
+
+        let path = format!(
+            "{}://{}:{}{}",
+            protocol, host, self.config.port, request_data.path
+        );
+        path
+    }
+
+    fn generate_default_headers(config: &ApiClientConfig) -> Result<HeaderMap, RequestError> {
+        let mut headers = HeaderMap::new();
+        let bearer_token = HeaderValue::from_str(format!("Bearer {}", config.api_key).as_str())
+            .map_err(|e| RequestError::Server(e.into()))?;
+        headers.insert(AUTHORIZATION, bearer_token);
+        Ok(headers)
+    }
+}
+
+#[cfg(feature = "client")]
+impl Transport for ReqwestTransport {
+    fn send(&self, request: RequestData) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let response = self.make_api_request(request).await?;
+            let status = response.status();
+            let body = response.text().await?;
+            Ok(RawResponse { status, body })
+        })
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
 mod api_client_tests {
     use crate::api_client::{
-        ApiClient, ApiClientConfig, CertificateHandling, HttpProtocol, DEFAULT_PORT,
+        gateway_config, ApiClient, ApiClientConfig, CertificateHandling, HttpProtocol,
+        ReqwestTransport, DEFAULT_PORT,
     };
+    use crate::commands::traits::RequestData;
+    use crate::err::cert::CertificateError;
+    use crate::err::http::RequestError;
     use rstest::*;
 
     #[fixture]
@@ -311,40 +497,130 @@ mod api_client_tests {
 
     #[tokio::test]
     async fn creates_api_client_with_new() {
-        let api_client = ApiClient::new(ApiClientConfig {
+        let transport = ReqwestTransport::new(ApiClientConfig {
             protocol: HttpProtocol::HTTP,
             port: 2000,
             url: "somedomain.com".to_string(),
             api_key: "my_key".to_string(),
-            cert_handling: CertificateHandling::DefaultCert,
+            cert_handling: CertificateHandling::DefaultCert(None),
+            tls_server_name: None,
+        })
+        .await
+        .expect("should create an ApiClient");
+        assert_eq!(transport.config.protocol, HttpProtocol::HTTP);
+        assert_eq!(transport.config.port, 2000);
+        assert_eq!(transport.config.url, "somedomain.com".to_string());
+        assert_eq!(transport.config.api_key, "my_key".to_string());
+        assert_eq!(
+            transport.config.cert_handling,
+            CertificateHandling::DefaultCert(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn creates_api_client_with_pinned_cert() {
+        let pin = "9a4ed18c3fcf9419ea40907400b386ac94d4f1729e360aa838cdda825ef06706";
+        let transport = ReqwestTransport::new(ApiClientConfig {
+            protocol: HttpProtocol::HTTPS,
+            port: 8443,
+            url: "192.168.1.20".to_string(),
+            api_key: "my_key".to_string(),
+            cert_handling: CertificateHandling::Pinned(pin.to_string()),
+            tls_server_name: None,
         })
         .await
         .expect("should create an ApiClient");
-        assert_eq!(api_client.config.protocol, HttpProtocol::HTTP);
-        assert_eq!(api_client.config.port, 2000);
-        assert_eq!(api_client.config.url, "somedomain.com".to_string());
-        assert_eq!(api_client.config.api_key, "my_key".to_string());
         assert_eq!(
-            api_client.config.cert_handling,
-            CertificateHandling::DefaultCert
+            transport.config.cert_handling,
+            CertificateHandling::Pinned(pin.to_string())
         );
+
+        let result = ReqwestTransport::new(ApiClientConfig {
+            cert_handling: CertificateHandling::Pinned("not a pin".to_string()),
+            ..transport.config.clone()
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(RequestError::Cert(CertificateError::InvalidPin(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn connects_to_ip_with_tls_server_name() {
+        let transport = ReqwestTransport::new(ApiClientConfig {
+            protocol: HttpProtocol::HTTPS,
+            port: 8443,
+            url: "192.168.1.20".to_string(),
+            api_key: "my_key".to_string(),
+            cert_handling: CertificateHandling::DefaultCert(None),
+            tls_server_name: Some("gateway-0000-1111-2222.local".to_string()),
+        })
+        .await
+        .expect("should create an ApiClient");
+
+        let request_data = RequestData {
+            path: "/enduser-mobile-web/1/enduserAPI/apiVersion".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            transport.generate_base_url(&request_data),
+            "https://gateway-0000-1111-2222.local:8443/enduser-mobile-web/1/enduserAPI/apiVersion"
+        );
+    }
+
+    #[tokio::test]
+    async fn brackets_ipv6_hosts_in_urls() {
+        let transport = ReqwestTransport::new(ApiClientConfig {
+            protocol: HttpProtocol::HTTP,
+            port: 8080,
+            url: "fe80::1".to_string(),
+            api_key: "my_key".to_string(),
+            cert_handling: CertificateHandling::NoCustomCert,
+            tls_server_name: None,
+        })
+        .await
+        .expect("should create an ApiClient");
+
+        let request_data = RequestData {
+            path: "/enduser-mobile-web/1/enduserAPI/apiVersion".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            transport.generate_base_url(&request_data),
+            "http://[fe80::1]:8080/enduser-mobile-web/1/enduserAPI/apiVersion"
+        );
+    }
+
+    #[tokio::test]
+    async fn errs_for_tls_server_name_without_ip() {
+        let result = ApiClient::new(ApiClientConfig {
+            protocol: HttpProtocol::HTTPS,
+            port: 8443,
+            url: "somedomain.com".to_string(),
+            api_key: "my_key".to_string(),
+            cert_handling: CertificateHandling::DefaultCert(None),
+            tls_server_name: Some("gateway-0000-1111-2222.local".to_string()),
+        })
+        .await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn creates_api_client_with_from() {
-        let api_client = ApiClient::from("0000-1111-2222", "my_key")
+        let transport = ReqwestTransport::new(gateway_config("0000-1111-2222", "my_key"))
             .await
             .expect("should create an ApiClient");
-        assert_eq!(api_client.config.port, DEFAULT_PORT);
+        assert_eq!(transport.config.port, DEFAULT_PORT);
         assert_eq!(
-            api_client.config.url,
+            transport.config.url,
             "gateway-0000-1111-2222.local".to_string()
         );
         assert_eq!(
-            api_client.config.cert_handling,
-            CertificateHandling::DefaultCert
+            transport.config.cert_handling,
+            CertificateHandling::DefaultCert(None)
         );
-        assert_eq!(api_client.config.protocol, HttpProtocol::HTTPS);
-        assert_eq!(api_client.config.api_key, "my_key".to_string());
+        assert_eq!(transport.config.protocol, HttpProtocol::HTTPS);
+        assert_eq!(transport.config.api_key, "my_key".to_string());
     }
 }
@@ -0,0 +1,49 @@
+use rumqttc::MqttOptions;
+use somfy_sdk::api_client::{ApiClient, ApiClientConfig};
+use somfy_sdk::mqtt::{run, BridgeConfig};
+use std::time::Duration;
+
+const ENV_MQTT_HOST: &str = "SOMFY_MQTT_HOST";
+const ENV_MQTT_PORT: &str = "SOMFY_MQTT_PORT";
+const ENV_MQTT_USERNAME: &str = "SOMFY_MQTT_USERNAME";
+const ENV_MQTT_PASSWORD: &str = "SOMFY_MQTT_PASSWORD";
+const ENV_MQTT_CLIENT_ID: &str = "SOMFY_MQTT_CLIENT_ID";
+const ENV_MQTT_PREFIX: &str = "SOMFY_MQTT_PREFIX";
+const ENV_MQTT_DISCOVERY_PREFIX: &str = "SOMFY_MQTT_DISCOVERY_PREFIX";
+
+// Usage: somfy-mqtt [config file], SOMFY_* environment variables take precedence
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = match std::env::args()
+        .nth(1)
+        .or(std::env::var("SOMFY_CONFIG").ok())
+    {
+        Some(path) => ApiClientConfig::from_file_and_env(path)?,
+        None => ApiClientConfig::from_env()?,
+    };
+    let api = ApiClient::new(config).await?;
+
+    let env = |name| std::env::var(name).ok();
+    let port = match env(ENV_MQTT_PORT) {
+        Some(port) => port.parse()?,
+        None => 1883,
+    };
+    let mut options = MqttOptions::new(
+        env(ENV_MQTT_CLIENT_ID).unwrap_or_else(|| "somfy-mqtt".to_string()),
+        env(ENV_MQTT_HOST).unwrap_or_else(|| "localhost".to_string()),
+        port,
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(username), Some(password)) = (env(ENV_MQTT_USERNAME), env(ENV_MQTT_PASSWORD)) {
+        options.set_credentials(username, password);
+    }
+
+    let mut bridge_config = BridgeConfig::default();
+    if let Some(prefix) = env(ENV_MQTT_PREFIX) {
+        bridge_config.prefix = prefix;
+    }
+    bridge_config.discovery_prefix = env(ENV_MQTT_DISCOVERY_PREFIX);
+
+    run(&api, options, bridge_config).await?;
+    Ok(())
+}
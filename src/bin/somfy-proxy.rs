@@ -0,0 +1,55 @@
+use somfy_sdk::api_client::{ApiClient, ApiClientConfig};
+use somfy_sdk::events::EventHub;
+use somfy_sdk::proxy::{router, ProxyConfig};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const ENV_PROXY_ADDR: &str = "SOMFY_PROXY_ADDR";
+const ENV_PROXY_API_KEYS: &str = "SOMFY_PROXY_API_KEYS";
+const ENV_PROXY_CORS_ORIGINS: &str = "SOMFY_PROXY_CORS_ORIGINS";
+
+// Usage: somfy-proxy [config file], SOMFY_* environment variables take precedence
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = match std::env::args()
+        .nth(1)
+        .or(std::env::var("SOMFY_CONFIG").ok())
+    {
+        Some(path) => ApiClientConfig::from_file_and_env(path)?,
+        None => ApiClientConfig::from_env()?,
+    };
+    let api = ApiClient::new(config).await?;
+
+    let list = |name| -> Vec<String> {
+        std::env::var(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let proxy_config = ProxyConfig {
+        api_keys: list(ENV_PROXY_API_KEYS),
+        cors_origins: list(ENV_PROXY_CORS_ORIGINS),
+    };
+    if proxy_config.api_keys.is_empty() {
+        anyhow::bail!("{ENV_PROXY_API_KEYS} must list at least one api key");
+    }
+
+    let addr: SocketAddr = std::env::var(ENV_PROXY_ADDR)
+        .unwrap_or_else(|_| "127.0.0.1:8080".to_string())
+        .parse()?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("Listening on http://{addr}");
+
+    // A single gateway listener shared by all /events subscribers
+    let events = EventHub::start(api.clone(), Duration::from_secs(1)).await?;
+    let served = axum::serve(listener, router(api, Some(events.clone()), &proxy_config))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await;
+    events.stop().await?;
+    Ok(served?)
+}
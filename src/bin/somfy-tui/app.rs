@@ -0,0 +1,252 @@
+use ratatui::crossterm::event::KeyCode;
+use somfy_sdk::commands::types::{
+    ActionGroupExecution, Device, Event, Setup, DEVICE_STATE_CHANGED_EVENT,
+};
+use somfy_sdk::device_url::DeviceUrl;
+
+const UNGROUPED: &str = "Other";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pane {
+    Devices,
+    Executions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intent {
+    Quit,
+    DeviceCommand {
+        device_url: DeviceUrl,
+        command: &'static str,
+    },
+    Cancel(String),
+    RefreshExecutions,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row {
+    Group(String),
+    Device(usize),
+}
+
+pub struct App {
+    // Sorted by group, then label
+    pub devices: Vec<(String, Device)>,
+    pub executions: Vec<ActionGroupExecution>,
+    pub selected_device: usize,
+    pub selected_execution: usize,
+    pub focus: Pane,
+    pub status: String,
+}
+
+impl App {
+    pub fn new(setup: Setup) -> Self {
+        let root_place = setup.root_place;
+        let mut devices: Vec<(String, Device)> = setup
+            .devices
+            .into_iter()
+            .map(|device| {
+                // Rooms if the gateway reports places, ui classes otherwise
+                let room = device
+                    .place_oid
+                    .as_deref()
+                    .and_then(|oid| root_place.as_ref()?.find(oid))
+                    .map(|place| place.label.clone());
+                let group = room
+                    .or_else(|| device.definition.as_ref().map(|d| d.ui_class.clone()))
+                    .unwrap_or_else(|| UNGROUPED.to_string());
+                (group, device)
+            })
+            .collect();
+        devices.sort_by(|(ga, a), (gb, b)| (ga, &a.label).cmp(&(gb, &b.label)));
+
+        Self {
+            devices,
+            executions: vec![],
+            selected_device: 0,
+            selected_execution: 0,
+            focus: Pane::Devices,
+            status: String::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        let mut current_group = None;
+        for (i, (group, _)) in self.devices.iter().enumerate() {
+            if current_group != Some(group) {
+                rows.push(Row::Group(group.clone()));
+                current_group = Some(group);
+            }
+            rows.push(Row::Device(i));
+        }
+        rows
+    }
+
+    pub fn selected_row(&self) -> Option<usize> {
+        self.rows()
+            .iter()
+            .position(|row| *row == Row::Device(self.selected_device))
+    }
+
+    pub fn selected(&self) -> Option<&Device> {
+        self.devices.get(self.selected_device).map(|(_, d)| d)
+    }
+
+    pub fn set_executions(&mut self, executions: Vec<ActionGroupExecution>) {
+        self.executions = executions;
+        self.selected_execution = self
+            .selected_execution
+            .min(self.executions.len().saturating_sub(1));
+    }
+
+    /// Applies state changes to the device list. Returns whether executions should be refetched.
+    pub fn apply_event(&mut self, event: &Event) -> bool {
+        if event.name != DEVICE_STATE_CHANGED_EVENT {
+            return event.exec_id.is_some();
+        }
+
+        let Some((_, device)) = self
+            .devices
+            .iter_mut()
+            .find(|(_, d)| Some(&d.device_url) == event.device_url.as_ref())
+        else {
+            return false;
+        };
+        for changed in &event.device_states {
+            match device.states.iter_mut().find(|s| s.name == changed.name) {
+                Some(state) => state.value = changed.value.clone(),
+                None => device.states.push(changed.clone()),
+            }
+        }
+        false
+    }
+
+    pub fn handle_key(&mut self, key: KeyCode) -> Option<Intent> {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Intent::Quit),
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Pane::Devices => Pane::Executions,
+                    Pane::Executions => Pane::Devices,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Char('r') => return Some(Intent::RefreshExecutions),
+            KeyCode::Char('o') => return self.device_command("open"),
+            KeyCode::Char('c') => return self.device_command("close"),
+            KeyCode::Char('s') => return self.device_command("stop"),
+            KeyCode::Char('m') => return self.device_command("my"),
+            KeyCode::Char('x') | KeyCode::Delete if self.focus == Pane::Executions => {
+                return self
+                    .executions
+                    .get(self.selected_execution)
+                    .map(|e| Intent::Cancel(e.id.clone()))
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let (selected, len) = match self.focus {
+            Pane::Devices => (&mut self.selected_device, self.devices.len()),
+            Pane::Executions => (&mut self.selected_execution, self.executions.len()),
+        };
+        *selected = selected
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+
+    fn device_command(&mut self, command: &'static str) -> Option<Intent> {
+        let device = self.selected()?;
+        if !device.supports_command(command) {
+            self.status = format!("{} does not support {command}", device.label);
+            return None;
+        }
+        Some(Intent::DeviceCommand {
+            device_url: device.device_url.clone(),
+            command,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Setup {
+        serde_json::from_str(
+            r#"{
+            "gateways": [],
+            "rootPlace": {"oid": "house", "label": "House", "subPlaces": [
+                {"oid": "kitchen", "label": "Kitchen"}
+            ]},
+            "devices": [
+                {"deviceURL": "io://1/2", "label": "Window", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+                 "states": [{"name": "core:ClosureState", "type": 1, "value": 0}], "attributes": [],
+                 "definition": {"uiClass": "RollerShutter", "type": "ACTUATOR", "widgetName": "",
+                    "attributes": [], "states": [], "commands": [{"commandName": "open", "nparams": 0}]}},
+                {"deviceURL": "io://1/1", "label": "Door", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+                 "states": [], "attributes": [], "placeOID": "kitchen"}
+            ]
+        }"#,
+        )
+        .expect("should parse setup")
+    }
+
+    #[test]
+    fn groups_devices_by_room_then_ui_class() {
+        let app = App::new(setup());
+        assert_eq!(
+            app.rows(),
+            vec![
+                Row::Group("Kitchen".to_string()),
+                Row::Device(0),
+                Row::Group("RollerShutter".to_string()),
+                Row::Device(1),
+            ]
+        );
+        assert_eq!(app.selected_row(), Some(1));
+    }
+
+    #[test]
+    fn applies_device_state_changes() {
+        let mut app = App::new(setup());
+        let event: Event = serde_json::from_str(
+            r#"{"name": "DeviceStateChangedEvent", "deviceURL": "io://1/2",
+                "deviceStates": [{"name": "core:ClosureState", "type": 1, "value": 100}]}"#,
+        )
+        .expect("should parse event");
+
+        assert!(!app.apply_event(&event));
+        let window = &app.devices[1].1;
+        assert_eq!(window.states.len(), 1);
+        assert_eq!(window.states[0].value.to_string(), "100");
+
+        let event: Event =
+            serde_json::from_str(r#"{"name": "ExecutionStateChangedEvent", "execId": "1"}"#)
+                .expect("should parse event");
+        assert!(app.apply_event(&event));
+    }
+
+    #[test]
+    fn maps_keys_to_commands() {
+        let mut app = App::new(setup());
+        app.handle_key(KeyCode::Down);
+
+        assert_eq!(
+            app.handle_key(KeyCode::Char('o')),
+            Some(Intent::DeviceCommand {
+                device_url: "io://1/2".parse().expect("should parse"),
+                command: "open",
+            })
+        );
+        assert_eq!(app.handle_key(KeyCode::Char('m')), None);
+        assert_eq!(app.status, "Window does not support my");
+        assert_eq!(app.handle_key(KeyCode::Char('q')), Some(Intent::Quit));
+    }
+}
@@ -0,0 +1,212 @@
+mod app;
+
+use app::{App, Intent, Pane, Row};
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use somfy_sdk::api_client::{ApiClient, ApiClientConfig};
+use somfy_sdk::commands::types::{Action, ActionGroup, Command};
+use somfy_sdk::events::{listener_expired, renew_listener};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const HELP: &str =
+    "↑/↓ select  tab switch pane  o open  c close  s stop  m my  x cancel  r refresh  q quit";
+
+// Usage: somfy-tui [config file], SOMFY_* environment variables take precedence
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = match std::env::args()
+        .nth(1)
+        .or(std::env::var("SOMFY_CONFIG").ok())
+    {
+        Some(path) => ApiClientConfig::from_file_and_env(path)?,
+        None => ApiClientConfig::from_env()?,
+    };
+    let client = ApiClient::new(config).await?;
+
+    let mut app = App::new(client.get_setup().await?);
+    app.set_executions(client.get_current_executions().await?);
+    let mut listener_id = client.register_event_listener().await?.id;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &client, &mut listener_id, &mut app).await;
+    ratatui::restore();
+
+    client.unregister_event_listener(&listener_id).await?;
+    result
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    client: &ApiClient,
+    listener_id: &mut String,
+    app: &mut App,
+) -> anyhow::Result<()> {
+    let mut last_poll: Option<Instant> = None;
+    loop {
+        if last_poll.is_none_or(|t| t.elapsed() >= POLL_INTERVAL) {
+            last_poll = Some(Instant::now());
+            match client.fetch_events(listener_id.as_str()).await {
+                Ok(events) => {
+                    let mut refresh = false;
+                    for event in &events {
+                        refresh |= app.apply_event(event);
+                    }
+                    if refresh {
+                        refresh_executions(client, app).await;
+                    }
+                }
+                // The gateway drops listeners that are not polled, e.g. while the terminal was
+                // suspended, events from that time are lost so executions are reloaded
+                Err(e) if listener_expired(&e) => match renew_listener(client, listener_id).await {
+                    Ok(renewed) => {
+                        *listener_id = renewed;
+                        app.status = "event listener expired, registered a new one".to_string();
+                        refresh_executions(client, app).await;
+                    }
+                    Err(e) => app.status = format!("registering event listener failed: {e}"),
+                },
+                Err(e) => app.status = format!("fetching events failed: {e}"),
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, app))?;
+
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        let TermEvent::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match app.handle_key(key.code) {
+            Some(Intent::Quit) => return Ok(()),
+            Some(Intent::DeviceCommand {
+                device_url,
+                command,
+            }) => {
+                let action_group = ActionGroup {
+                    label: Some(format!("somfy-tui: {command}")),
+                    actions: vec![Action {
+                        device_url,
+                        commands: vec![Command {
+                            name: command.to_string(),
+                            parameters: vec![],
+                        }],
+                    }],
+                };
+                app.status = match client.execute_actions(&action_group).await {
+                    Ok(execution) => format!("{command} started: {}", execution.exec_id),
+                    Err(e) => format!("{command} failed: {e}"),
+                };
+                refresh_executions(client, app).await;
+            }
+            Some(Intent::Cancel(id)) => {
+                app.status = match client.cancel_execution(&id).await {
+                    Ok(_) => format!("cancelled {id}"),
+                    Err(e) => format!("cancelling {id} failed: {e}"),
+                };
+                refresh_executions(client, app).await;
+            }
+            Some(Intent::RefreshExecutions) => refresh_executions(client, app).await,
+            None => {}
+        }
+    }
+}
+
+async fn refresh_executions(client: &ApiClient, app: &mut App) {
+    match client.get_current_executions().await {
+        Ok(executions) => app.set_executions(executions),
+        Err(e) => app.status = format!("fetching executions failed: {e}"),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [body, footer] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [devices_area, right] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(body);
+    let [states_area, executions_area] =
+        Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+    let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+    let devices: Vec<ListItem> = app
+        .rows()
+        .into_iter()
+        .map(|row| match row {
+            Row::Group(group) => ListItem::new(Line::from(group).bold()),
+            Row::Device(i) => {
+                let device = &app.devices[i].1;
+                let marker = if device.available { " " } else { "!" };
+                ListItem::new(format!(" {marker} {}", device.label))
+            }
+        })
+        .collect();
+    let mut devices_state = ListState::default().with_selected(app.selected_row());
+    frame.render_stateful_widget(
+        List::new(devices)
+            .block(pane_block("Devices", app.focus == Pane::Devices))
+            .highlight_style(highlight),
+        devices_area,
+        &mut devices_state,
+    );
+
+    let states: Vec<Line> = app
+        .selected()
+        .map(|device| {
+            std::iter::once(Line::from(device.device_url.to_string()).italic())
+                .chain(
+                    device
+                        .states
+                        .iter()
+                        .map(|s| Line::from(format!("{}: {}", s.name, s.value))),
+                )
+                .collect()
+        })
+        .unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(states).block(Block::bordered().title("States")),
+        states_area,
+    );
+
+    let executions: Vec<ListItem> = app
+        .executions
+        .iter()
+        .map(|e| {
+            let label = e.action_group.label.as_deref().unwrap_or(&e.id);
+            ListItem::new(format!("{} {label}", e.state))
+        })
+        .collect();
+    let mut executions_state = ListState::default()
+        .with_selected((!app.executions.is_empty()).then_some(app.selected_execution));
+    frame.render_stateful_widget(
+        List::new(executions)
+            .block(pane_block("Executions", app.focus == Pane::Executions))
+            .highlight_style(highlight),
+        executions_area,
+        &mut executions_state,
+    );
+
+    let footer_text = if app.status.is_empty() {
+        HELP
+    } else {
+        &app.status
+    };
+    frame.render_widget(Paragraph::new(footer_text).dim(), footer);
+}
+
+fn pane_block(title: &str, focused: bool) -> Block<'_> {
+    let block = Block::bordered().title(title);
+    if focused {
+        block.border_style(Style::default().bold())
+    } else {
+        block
+    }
+}
@@ -0,0 +1,284 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use somfy_sdk::api_client::{ApiClient, ApiClientConfig};
+use somfy_sdk::commands::types::{Action, ActionGroup, Command};
+use somfy_sdk::device_url::DeviceUrl;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "somfy",
+    version,
+    about = "Control a Somfy TaHoma gateway from the command line"
+)]
+struct Cli {
+    /// Config file (.toml or .json), SOMFY_* environment variables take precedence
+    #[arg(long, short, global = true, env = "SOMFY_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[arg(long, short, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// List all devices
+    Devices,
+    /// Show a single device
+    Device { device_url: DeviceUrl },
+    /// Show the states of a device
+    States { device_url: DeviceUrl },
+    /// Execute a command on a device
+    Exec {
+        device_url: DeviceUrl,
+        command: String,
+        args: Vec<String>,
+    },
+    /// List running executions
+    Executions,
+    /// Cancel an execution, or all executions if no id is given
+    Cancel { execution_id: Option<String> },
+    /// Print events from the gateway
+    Events {
+        /// Keep polling for events until interrupted
+        #[arg(long, short)]
+        follow: bool,
+        /// Seconds between polls when following
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {e}");
+        let mut source = e.source();
+        while let Some(cause) = source {
+            eprintln!("  caused by: {cause}");
+            source = cause.source();
+        }
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = match &cli.config {
+        Some(path) => ApiClientConfig::from_file_and_env(path)?,
+        None => ApiClientConfig::from_env()?,
+    };
+    let client = ApiClient::new(config).await?;
+    let out = cli.output;
+
+    match cli.command {
+        Cmd::Devices => {
+            let devices = client.get_devices().await?;
+            let rows = devices
+                .iter()
+                .map(|d| {
+                    vec![
+                        d.label.clone(),
+                        d.device_url.to_string(),
+                        d.controllable_name.clone(),
+                        d.available.to_string(),
+                    ]
+                })
+                .collect();
+            print(
+                out,
+                &devices,
+                &["LABEL", "DEVICE URL", "CONTROLLABLE", "AVAILABLE"],
+                rows,
+            )?;
+        }
+        Cmd::Device { device_url } => {
+            let device = client.get_device(&device_url).await?;
+            let rows = vec![
+                vec!["label".to_string(), device.label.clone()],
+                vec!["device url".to_string(), device.device_url.to_string()],
+                vec!["controllable".to_string(), device.controllable_name.clone()],
+                vec!["available".to_string(), device.available.to_string()],
+                vec!["synced".to_string(), device.synced.to_string()],
+                vec!["enabled".to_string(), device.enabled.to_string()],
+            ];
+            print(out, &device, &["FIELD", "VALUE"], rows)?;
+        }
+        Cmd::States { device_url } => {
+            let states = client.get_device_states(&device_url).await?;
+            let rows = states
+                .iter()
+                .map(|s| vec![s.name.clone(), s.value.to_string()])
+                .collect();
+            print(out, &states, &["NAME", "VALUE"], rows)?;
+        }
+        Cmd::Exec {
+            device_url,
+            command,
+            args,
+        } => {
+            let action_group = action_group(device_url, command, args);
+            let execution = client.execute_actions(&action_group).await?;
+            let rows = vec![vec![execution.exec_id.clone()]];
+            print(out, &execution, &["EXECUTION ID"], rows)?;
+        }
+        Cmd::Executions => {
+            let executions = client.get_current_executions().await?;
+            let rows = executions
+                .iter()
+                .map(|e| {
+                    vec![
+                        e.id.clone(),
+                        e.state.clone(),
+                        e.action_group.label.clone().unwrap_or_default(),
+                        e.start_time.to_string(),
+                    ]
+                })
+                .collect();
+            print(
+                out,
+                &executions,
+                &["ID", "STATE", "LABEL", "START TIME"],
+                rows,
+            )?;
+        }
+        Cmd::Cancel { execution_id } => match execution_id {
+            Some(id) => {
+                let result = client.cancel_execution(&id).await?;
+                print(out, &result, &["CANCELLED"], vec![vec![id]])?;
+            }
+            None => {
+                let result = client.cancel_all_executions().await?;
+                print(out, &result, &["CANCELLED"], vec![vec!["all".to_string()]])?;
+            }
+        },
+        Cmd::Events { follow, interval } => {
+            let listener = client.register_event_listener().await?;
+            let result = follow_events(&client, &listener.id, follow, interval).await;
+            client.unregister_event_listener(&listener.id).await?;
+            result?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn follow_events(
+    client: &ApiClient,
+    listener_id: &str,
+    follow: bool,
+    interval: u64,
+) -> anyhow::Result<()> {
+    loop {
+        // Events are printed one JSON object per line in both output modes, so they can be piped
+        for event in client.fetch_events(listener_id).await? {
+            println!("{}", serde_json::to_string(&event)?);
+        }
+        if !follow {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(interval)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+fn action_group(device_url: DeviceUrl, command: String, args: Vec<String>) -> ActionGroup {
+    ActionGroup {
+        label: Some(format!("somfy cli: {command}")),
+        actions: vec![Action {
+            device_url,
+            commands: vec![Command {
+                name: command,
+                parameters: args,
+            }],
+        }],
+    }
+}
+
+fn print<T: Serialize>(
+    out: Output,
+    value: &T,
+    header: &[&str],
+    rows: Vec<Vec<String>>,
+) -> anyhow::Result<()> {
+    match out {
+        Output::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Output::Table => print!("{}", table(header, &rows)),
+    }
+    Ok(())
+}
+
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    std::iter::once(&header)
+        .chain(rows)
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verifies_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_exec_arguments() {
+        let cli = Cli::parse_from(["somfy", "exec", "io://1234/5678", "setClosure", "50"]);
+        let Cmd::Exec {
+            device_url,
+            command,
+            args,
+        } = cli.command
+        else {
+            panic!("expected exec command");
+        };
+
+        let group = action_group(device_url, command, args);
+        assert_eq!(group.actions[0].device_url, "io://1234/5678");
+        assert_eq!(group.actions[0].commands[0].name, "setClosure");
+        assert_eq!(group.actions[0].commands[0].parameters, vec!["50"]);
+    }
+
+    #[test]
+    fn aligns_table_columns() {
+        let rows = vec![
+            vec!["Living room".to_string(), "io://1".to_string()],
+            vec!["Office".to_string(), "io://22".to_string()],
+        ];
+        assert_eq!(
+            table(&["LABEL", "URL"], &rows),
+            "LABEL        URL\nLiving room  io://1\nOffice       io://22\n"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::api_client::{ApiClient, ApiClientConfig};
+use crate::cassette::Cassette;
+use crate::commands::cancel_all_executions::{
+    CancelAllExecutionsCommand, CancelAllExecutionsResponse,
+};
+use crate::commands::cancel_execution::{CancelExecutionCommand, CancelExecutionResponse};
+use crate::commands::fetch_events::{FetchEventsCommand, FetchEventsResponse};
+use crate::commands::get_current_executions::{
+    GetCurrentExecutionsCommand, GetCurrentExecutionsResponse,
+};
+use crate::commands::get_device::{GetDeviceCommand, GetDeviceResponse};
+use crate::commands::get_device_state::{GetDeviceStateCommand, GetDeviceStateResponse};
+use crate::commands::get_device_states::{GetDeviceStatesCommand, GetDeviceStatesResponse};
+use crate::commands::get_devices::{GetDevicesCommand, GetDevicesResponse};
+use crate::commands::get_devices_by_controllable::{
+    GetDevicesByControllableCommand, GetDevicesByControllableResponse,
+};
+use crate::commands::get_execution::{GetExecutionCommand, GetExecutionResponse};
+use crate::commands::get_setup::{GetSetupCommand, GetSetupResponse};
+use crate::commands::get_setup_gateways::{GetGatewaysCommand, GetGatewaysResponse};
+use crate::commands::get_version::{GetVersionCommand, GetVersionResponse};
+use crate::commands::register_event_listener::{
+    RegisterEventListenerCommand, RegisterEventListenerResponse,
+};
+use crate::commands::traits::{SomfyApiRequestCommand, SomfyApiRequestResponse};
+use crate::commands::unregister_event_listener::{
+    UnregisterEventListenerCommand, UnregisterEventListenerResponse,
+};
+use crate::device_url::DeviceUrl;
+use crate::err::http::RequestError;
+use crate::transport::Transport;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::runtime::Builder;
+use tokio::sync::mpsc;
+
+/// A synchronous `ApiClient`. Requests run on a runtime on a thread of its own, shared by all
+/// clones, so connections keep being driven between calls.
+///
+/// Calls block the calling thread, so like `reqwest::blocking` it is not meant to be used from
+/// async code.
+#[derive(Debug, Clone)]
+pub struct BlockingApiClient {
+    inner: ApiClient,
+    runtime: Arc<RuntimeThread>,
+}
+
+impl BlockingApiClient {
+    pub fn new(config: ApiClientConfig) -> Result<Self, RequestError> {
+        let runtime = RuntimeThread::start()?;
+        let inner = runtime.run(ApiClient::new(config))?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    pub fn from(id: &str, api_key: &str) -> Result<Self, RequestError> {
+        let (id, api_key) = (id.to_string(), api_key.to_string());
+        let runtime = RuntimeThread::start()?;
+        let inner = runtime.run(async move { ApiClient::from(&id, &api_key).await })?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// See `ApiClient::recording`.
+    pub fn recording(
+        config: ApiClientConfig,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, RequestError> {
+        let path = path.as_ref().to_path_buf();
+        let runtime = RuntimeThread::start()?;
+        let inner = runtime.run(ApiClient::recording(config, path))?;
+        Ok(Self {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// See `ApiClient::replaying`.
+    pub fn replaying(cassette: Cassette) -> Result<Self, RequestError> {
+        Ok(Self {
+            inner: ApiClient::replaying(cassette),
+            runtime: Arc::new(RuntimeThread::start()?),
+        })
+    }
+
+    pub fn with_transport(transport: impl Transport + 'static) -> Result<Self, RequestError> {
+        Ok(Self {
+            inner: ApiClient::with_transport(transport),
+            runtime: Arc::new(RuntimeThread::start()?),
+        })
+    }
+
+    // Only the request is sent to the runtime thread, so commands may borrow their arguments
+    pub fn execute<C>(&self, command: C) -> Result<C::Response, RequestError>
+    where
+        C: SomfyApiRequestCommand,
+    {
+        let request_data = command.to_request()?;
+        let inner = self.inner.clone();
+        let response = self
+            .runtime
+            .run(async move { inner.send(request_data).await })?;
+        C::Response::from_response(response.status, &response.body)
+    }
+
+    pub fn get_version(&self) -> Result<GetVersionResponse, RequestError> {
+        self.execute(GetVersionCommand)
+    }
+
+    pub fn get_gateways(&self) -> Result<GetGatewaysResponse, RequestError> {
+        self.execute(GetGatewaysCommand)
+    }
+
+    pub fn get_devices(&self) -> Result<GetDevicesResponse, RequestError> {
+        self.execute(GetDevicesCommand)
+    }
+
+    pub fn get_device(&self, device_url: &DeviceUrl) -> Result<GetDeviceResponse, RequestError> {
+        self.execute(GetDeviceCommand { device_url })
+    }
+
+    pub fn get_setup(&self) -> Result<GetSetupResponse, RequestError> {
+        self.execute(GetSetupCommand)
+    }
+
+    pub fn get_device_states(
+        &self,
+        device_url: &DeviceUrl,
+    ) -> Result<GetDeviceStatesResponse, RequestError> {
+        self.execute(GetDeviceStatesCommand { device_url })
+    }
+
+    pub fn get_device_state(
+        &self,
+        device_url: &DeviceUrl,
+        state_name: &str,
+    ) -> Result<GetDeviceStateResponse, RequestError> {
+        self.execute(GetDeviceStateCommand {
+            device_url,
+            state_name,
+        })
+    }
+
+    pub fn get_devices_by_controllable(
+        &self,
+        controllable_name: &str,
+    ) -> Result<GetDevicesByControllableResponse, RequestError> {
+        self.execute(GetDevicesByControllableCommand { controllable_name })
+    }
+
+    pub fn register_event_listener(&self) -> Result<RegisterEventListenerResponse, RequestError> {
+        self.execute(RegisterEventListenerCommand)
+    }
+
+    pub fn fetch_events(&self, listener_id: &str) -> Result<FetchEventsResponse, RequestError> {
+        self.execute(FetchEventsCommand { listener_id })
+    }
+
+    pub fn unregister_event_listener(
+        &self,
+        listener_id: &str,
+    ) -> Result<UnregisterEventListenerResponse, RequestError> {
+        self.execute(UnregisterEventListenerCommand { listener_id })
+    }
+
+    #[cfg(feature = "generic-exec")]
+    pub fn execute_actions(
+        &self,
+        action_group: &crate::commands::types::ActionGroup,
+    ) -> Result<crate::commands::execute_action_group::ExecuteActionGroupResponse, RequestError>
+    {
+        self.execute(
+            crate::commands::execute_action_group::ExecuteActionGroupCommand { action_group },
+        )
+    }
+
+    pub fn get_current_executions(&self) -> Result<GetCurrentExecutionsResponse, RequestError> {
+        self.execute(GetCurrentExecutionsCommand)
+    }
+
+    pub fn get_execution(&self, execution_id: &str) -> Result<GetExecutionResponse, RequestError> {
+        self.execute(GetExecutionCommand { execution_id })
+    }
+
+    pub fn cancel_all_executions(&self) -> Result<CancelAllExecutionsResponse, RequestError> {
+        self.execute(CancelAllExecutionsCommand)
+    }
+
+    pub fn cancel_execution(
+        &self,
+        execution_id: &str,
+    ) -> Result<CancelExecutionResponse, RequestError> {
+        self.execute(CancelExecutionCommand { execution_id })
+    }
+}
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// A current thread runtime driven by a thread of its own, like `reqwest::blocking` does. The
+// runtime is dropped on that thread, so dropping the client within async code does not panic.
+#[derive(Debug)]
+struct RuntimeThread {
+    jobs: Option<mpsc::UnboundedSender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RuntimeThread {
+    fn start() -> Result<Self, RequestError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| RequestError::Server(e.into()))?;
+        let (jobs, mut received) = mpsc::unbounded_channel::<Job>();
+        let thread = std::thread::Builder::new()
+            .name("somfy-sdk-blocking".to_string())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    while let Some(job) = received.recv().await {
+                        tokio::spawn(job);
+                    }
+                })
+            })
+            .map_err(|e| RequestError::Server(e.into()))?;
+        Ok(Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        })
+    }
+
+    fn run<T, F>(&self, future: F) -> Result<T, RequestError>
+    where
+        T: Send + 'static,
+        F: Future<Output = Result<T, RequestError>> + Send + 'static,
+    {
+        let stopped = || RequestError::Server(anyhow::anyhow!("blocking runtime stopped"));
+        let (result, receiver) = std::sync::mpsc::sync_channel(1);
+        let job = Box::pin(async move {
+            let _ = result.send(future.await);
+        });
+        self.jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send(job).ok())
+            .ok_or_else(stopped)?;
+        receiver.recv().map_err(|_| stopped())?
+    }
+}
+
+impl Drop for RuntimeThread {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::traits::HttpMethod;
+    use crate::transport::{MockTransport, RawResponse};
+
+    #[test]
+    fn runs_requests_without_a_runtime() {
+        let mock = MockTransport::new().respond(
+            HttpMethod::GET,
+            "/setup/devices/controllables/io%3ALight",
+            RawResponse::ok(r#"["io://0000-1111-2222/3"]"#),
+        );
+        let client = BlockingApiClient::with_transport(mock).expect("should create client");
+
+        let urls = client
+            .get_devices_by_controllable("io:Light")
+            .expect("should get devices");
+        assert_eq!(urls, vec!["io://0000-1111-2222/3"]);
+        assert!(client.get_setup().is_err());
+    }
+
+    #[test]
+    fn drops_within_async_context() {
+        let client =
+            BlockingApiClient::replaying(Cassette::default()).expect("should create client");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("should build runtime");
+        runtime.block_on(async move { drop(client) });
+    }
+}
@@ -0,0 +1,425 @@
+use crate::commands::traits::{HttpMethod, RequestData};
+use crate::err::cassette::CassetteError;
+use crate::err::http::RequestError;
+use crate::transport::{RawResponse, Transport, TransportFuture};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Version of the cassette format, other versions are rejected when loading.
+pub const CASSETTE_VERSION: u32 = 1;
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    // Sorted, so recordings don't depend on the order parameters were added in
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl RecordedRequest {
+    /// The request as sent with `api_key`, which is redacted wherever it appears.
+    pub(crate) fn new(request: &RequestData, api_key: &str) -> Self {
+        let redact = |s: &str| match api_key.is_empty() {
+            true => s.to_string(),
+            false => s.replace(api_key, REDACTED),
+        };
+        let mut headers: BTreeMap<String, String> = request
+            .header_map
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), redact(value.to_str().ok()?))))
+            .collect();
+        headers.insert("authorization".to_string(), format!("Bearer {REDACTED}"));
+
+        let query = request
+            .query_params
+            .iter()
+            .map(|(name, value)| (name.clone(), redact(value)))
+            .collect();
+        let body = Some(request.body.as_bytes())
+            .filter(|b| !b.is_empty())
+            .map(|b| redact(&String::from_utf8_lossy(b)));
+        Self {
+            method: method_name(&request.method).to_string(),
+            path: redact(&request.path),
+            query,
+            headers,
+            body,
+        }
+    }
+
+    // Headers are ignored, JSON bodies are compared by value
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        let json = |body: &Option<String>| {
+            body.as_deref()
+                .map(|b| serde_json::from_str::<Value>(b).unwrap_or(Value::String(b.to_string())))
+        };
+        self.method == other.method
+            && self.path == other.path
+            && self.query == other.query
+            && json(&self.body) == json(&other.body)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+/// Request/response pairs recorded from a gateway, see `RecordingTransport` and
+/// `ReplayTransport`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cassette {
+    interactions: Vec<Interaction>,
+    // Interactions already served when replaying
+    played: Vec<bool>,
+    // Saved after every recorded interaction if set
+    path: Option<PathBuf>,
+}
+
+impl Cassette {
+    pub fn new(interactions: Vec<Interaction>) -> Self {
+        Self {
+            played: vec![false; interactions.len()],
+            interactions,
+            path: None,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CassetteError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|source| CassetteError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let file: CassetteFile = serde_json::from_str(&json)?;
+        if file.version != CASSETTE_VERSION {
+            return Err(CassetteError::UnsupportedVersion(file.version));
+        }
+        Ok(Self::new(file.interactions))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CassetteError> {
+        let path = path.as_ref();
+        let file = CassetteFile {
+            version: CASSETTE_VERSION,
+            interactions: self.interactions.clone(),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&file)?).map_err(|source| {
+            CassetteError::Io {
+                path: path.to_path_buf(),
+                source,
+            }
+        })
+    }
+
+    pub fn interactions(&self) -> &[Interaction] {
+        &self.interactions
+    }
+
+    /// Interactions not replayed yet, e.g. to check that a test made every recorded request.
+    pub fn unplayed(&self) -> impl Iterator<Item = &Interaction> {
+        self.interactions
+            .iter()
+            .zip(&self.played)
+            .filter_map(|(interaction, played)| (!played).then_some(interaction))
+    }
+
+    /// An empty cassette that is written to `path` after every recorded interaction.
+    pub(crate) fn recording_to(path: impl AsRef<Path>) -> Result<Self, CassetteError> {
+        let cassette = Self {
+            path: Some(path.as_ref().to_path_buf()),
+            ..Self::default()
+        };
+        cassette.save(path)?;
+        Ok(cassette)
+    }
+
+    pub(crate) fn record(&mut self, interaction: Interaction) -> Result<(), CassetteError> {
+        self.interactions.push(interaction);
+        self.played.push(true);
+        match &self.path {
+            Some(path) => self.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// The first response not served yet whose request matches, so repeated requests are
+    /// answered in recording order.
+    pub(crate) fn replay(
+        &mut self,
+        request: &RecordedRequest,
+    ) -> Result<RecordedResponse, CassetteError> {
+        let index = self
+            .interactions
+            .iter()
+            .zip(&self.played)
+            .position(|(i, played)| !played && i.request.matches(request))
+            .ok_or_else(|| CassetteError::NoMatch {
+                method: request.method.clone(),
+                path: request.path.clone(),
+            })?;
+        self.played[index] = true;
+        Ok(self.interactions[index].response.clone())
+    }
+}
+
+/// Sends requests through another transport and records them with their responses. Clones
+/// share the cassette, so a clone can be inspected after handing one to `ApiClient`.
+#[derive(Debug, Clone)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    cassette: Arc<Mutex<Cassette>>,
+    api_key: String,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Records to a new cassette at `path`, which is saved after every interaction. `api_key`
+    /// is redacted from the recording.
+    pub fn new(inner: T, path: impl AsRef<Path>, api_key: &str) -> Result<Self, CassetteError> {
+        Ok(Self {
+            inner,
+            cassette: Arc::new(Mutex::new(Cassette::recording_to(path)?)),
+            api_key: api_key.to_string(),
+        })
+    }
+
+    /// The interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        lock(&self.cassette).clone()
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send(&self, request: RequestData) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let recorded = RecordedRequest::new(&request, &self.api_key);
+            let response = self.inner.send(request).await?;
+            lock(&self.cassette)
+                .record(Interaction {
+                    request: recorded,
+                    response: RecordedResponse {
+                        status: response.status.as_u16(),
+                        body: response.body.clone(),
+                    },
+                })
+                .map_err(|e| RequestError::Server(e.into()))?;
+            Ok(response)
+        })
+    }
+}
+
+/// Answers requests from a cassette without connecting to a gateway. Requests are matched on
+/// method, path, query and body, and requests without a recorded response fail. Clones share
+/// the cassette, like `RecordingTransport`.
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    cassette: Arc<Mutex<Cassette>>,
+    api_key: String,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            cassette: Arc::new(Mutex::new(cassette)),
+            api_key: String::new(),
+        }
+    }
+
+    /// Redacts `api_key` from requests like `RecordingTransport` did, so requests carrying the
+    /// key match their recording.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    /// The cassette with the interactions served so far marked as played, see
+    /// `Cassette::unplayed`.
+    pub fn cassette(&self) -> Cassette {
+        lock(&self.cassette).clone()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: RequestData) -> TransportFuture<'_> {
+        let response = lock(&self.cassette)
+            .replay(&RecordedRequest::new(&request, &self.api_key))
+            .map(|r| {
+                let status =
+                    StatusCode::from_u16(r.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                RawResponse::new(status, r.body)
+            })
+            .map_err(|e| RequestError::Server(e.into()));
+        Box::pin(async move { response })
+    }
+}
+
+// Cassettes stay usable after a panic while recording or replaying
+fn lock(cassette: &Mutex<Cassette>) -> MutexGuard<'_, Cassette> {
+    cassette.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn method_name(method: &HttpMethod) -> &'static str {
+    match method {
+        HttpMethod::GET => "GET",
+        HttpMethod::POST => "POST",
+        HttpMethod::DELETE => "DELETE",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: HttpMethod, path: &str, body: &'static str) -> RequestData {
+        RequestData {
+            method,
+            path: path.to_string(),
+            body: body.into(),
+            ..RequestData::default()
+        }
+    }
+
+    fn response(body: &str) -> RecordedResponse {
+        RecordedResponse {
+            status: 200,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn redacts_api_key() {
+        let recorded = RecordedRequest::new(
+            &request(
+                HttpMethod::POST,
+                "/exec/apply",
+                r#"{"label": "secret-key"}"#,
+            ),
+            "secret-key",
+        );
+        assert_eq!(recorded.headers["authorization"], "Bearer [REDACTED]");
+        assert_eq!(recorded.body.as_deref(), Some(r#"{"label": "[REDACTED]"}"#));
+    }
+
+    #[test]
+    fn replays_matching_requests_in_order() {
+        let fetch = RecordedRequest::new(&request(HttpMethod::POST, "/events/1/fetch", ""), "");
+        let apply = RecordedRequest::new(
+            &request(HttpMethod::POST, "/exec/apply", r#"{"actions": []}"#),
+            "",
+        );
+        let mut cassette = Cassette::new(vec![
+            Interaction {
+                request: fetch.clone(),
+                response: response("[1]"),
+            },
+            Interaction {
+                request: apply.clone(),
+                response: response("{}"),
+            },
+            Interaction {
+                request: fetch.clone(),
+                response: response("[2]"),
+            },
+        ]);
+
+        assert_eq!(cassette.replay(&fetch).expect("should match").body, "[1]");
+        assert_eq!(cassette.replay(&fetch).expect("should match").body, "[2]");
+        assert!(cassette.replay(&fetch).is_err());
+
+        // Bodies are compared as JSON
+        let reformatted = RecordedRequest {
+            body: Some(r#"{ "actions":[] }"#.to_string()),
+            ..apply
+        };
+        assert_eq!(
+            cassette.replay(&reformatted).expect("should match").body,
+            "{}"
+        );
+    }
+
+    #[tokio::test]
+    async fn shares_cassette_between_clones() {
+        let devices = RecordedRequest::new(
+            &request(
+                HttpMethod::GET,
+                "/enduser-mobile-web/1/enduserAPI/setup/devices",
+                "",
+            ),
+            "",
+        );
+        let transport = ReplayTransport::new(Cassette::new(vec![Interaction {
+            request: devices,
+            response: response("[]"),
+        }]));
+        assert_eq!(transport.cassette().unplayed().count(), 1);
+
+        let client = crate::api_client::ApiClient::with_transport(transport.clone());
+        client.get_devices().await.expect("should replay");
+        assert_eq!(transport.cassette().unplayed().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn redacts_api_key_when_replaying() {
+        let with_key = || request(HttpMethod::POST, "/exec/apply", r#"{"label": "secret"}"#);
+        let cassette = Cassette::new(vec![Interaction {
+            request: RecordedRequest::new(&with_key(), "secret"),
+            response: response(r#"{"execId": "1"}"#),
+        }]);
+
+        let without_key = ReplayTransport::new(cassette.clone());
+        assert!(without_key.send(with_key()).await.is_err());
+        let transport = ReplayTransport::new(cassette).with_api_key("secret");
+        assert!(transport.send(with_key()).await.is_ok());
+    }
+
+    #[test]
+    fn matches_query_parameters() {
+        let with_query = |params: &[(&str, &str)]| {
+            let mut request = request(HttpMethod::GET, "/history/executions", "");
+            request.query_params = params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            RecordedRequest::new(&request, "")
+        };
+        let recorded = with_query(&[("limit", "10"), ("offset", "0")]);
+        assert_eq!(
+            serde_json::to_value(&recorded).expect("should serialize")["query"],
+            serde_json::json!({ "limit": "10", "offset": "0" })
+        );
+
+        let mut cassette = Cassette::new(vec![Interaction {
+            request: recorded,
+            response: response("[]"),
+        }]);
+        assert!(cassette
+            .replay(&with_query(&[("limit", "10"), ("offset", "20")]))
+            .is_err());
+        assert!(cassette
+            .replay(&with_query(&[("offset", "0"), ("limit", "10")]))
+            .is_ok());
+    }
+}
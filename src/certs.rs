@@ -0,0 +1,176 @@
+use crate::config::tls_cert::{TlsCertHandler, OVERKIZ_ROOT_CA_SHA256};
+use crate::err::cert::CertificateError;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use x509_parser::pem::parse_x509_pem;
+
+// Manages the cert cache used by `CertificateHandling::DefaultCert`. All functions take the
+// same optional cache dir as `DefaultCert`, `None` resolves the default location.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    /// SHA-256 over the DER encoding, hex encoded
+    pub sha256_fingerprint: String,
+}
+
+impl CertificateInfo {
+    pub fn from_pem(pem: &[u8]) -> Result<Self, CertificateError> {
+        let (_, pem) =
+            parse_x509_pem(pem).map_err(|e| CertificateError::Malformed(e.to_string()))?;
+        let cert = pem
+            .parse_x509()
+            .map_err(|e| CertificateError::Malformed(e.to_string()))?;
+        let validity = cert.validity();
+
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            not_before: validity.not_before.to_string(),
+            not_after: validity.not_after.to_string(),
+            sha256_fingerprint: TlsCertHandler::fingerprint(&pem.contents),
+        })
+    }
+}
+
+/// Path of the cached cert file, whether or not it exists.
+pub fn cache_location(cache_dir: Option<&Path>) -> Option<PathBuf> {
+    TlsCertHandler::get_folder_location(cache_dir).map(|f| TlsCertHandler::get_file_location(&f))
+}
+
+/// Describes the cached cert, or returns `None` if nothing is cached yet.
+pub fn inspect(cache_dir: Option<&Path>) -> Result<Option<CertificateInfo>, CertificateError> {
+    let path = cache_location(cache_dir).ok_or(CertificateError::NoCacheLocation)?;
+    match std::fs::read(&path) {
+        Ok(pem) => CertificateInfo::from_pem(&pem).map(Some),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(CertificateError::CacheUnreadable { path, source }),
+    }
+}
+
+/// Downloads the Overkiz root CA into the cache, replacing any cached cert.
+#[cfg(feature = "cert-download")]
+pub async fn refresh(cache_dir: Option<&Path>) -> Result<CertificateInfo, CertificateError> {
+    let folder =
+        TlsCertHandler::get_folder_location(cache_dir).ok_or(CertificateError::NoCacheLocation)?;
+    let path = TlsCertHandler::get_file_location(&folder);
+    TlsCertHandler::ensure_local_folder(folder)?;
+    TlsCertHandler::download_cert(&path).await?;
+
+    inspect(cache_dir)?.ok_or(CertificateError::NoCertAvailable)
+}
+
+/// Copies a PEM encoded cert into the cache after checking it is the currently valid Overkiz
+/// root CA, the only cert `DefaultCert` trusts from the cache.
+pub fn import_pem(
+    pem_path: impl AsRef<Path>,
+    cache_dir: Option<&Path>,
+) -> Result<CertificateInfo, CertificateError> {
+    let pem_path = pem_path.as_ref();
+    let pem = std::fs::read(pem_path).map_err(|source| CertificateError::Unreadable {
+        path: pem_path.to_path_buf(),
+        source,
+    })?;
+    TlsCertHandler::verify_pem(&pem, Some(OVERKIZ_ROOT_CA_SHA256))?;
+
+    let folder =
+        TlsCertHandler::get_folder_location(cache_dir).ok_or(CertificateError::NoCacheLocation)?;
+    let path = TlsCertHandler::get_file_location(&folder);
+    TlsCertHandler::ensure_local_folder(folder)?;
+    TlsCertHandler::write_atomic(&path, &pem)?;
+
+    CertificateInfo::from_pem(&pem)
+}
+
+/// Removes the cached cert. Returns whether there was a cert to remove.
+pub fn clear(cache_dir: Option<&Path>) -> Result<bool, CertificateError> {
+    let path = cache_location(cache_dir).ok_or(CertificateError::NoCacheLocation)?;
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(CertificateError::FileSystemError(e.into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE_CERT: &str = "./tests/fixtures/cert/overkiz-root-ca-2048.crt";
+
+    fn cache_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("somfy_sdk_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn describes_overkiz_root_ca() {
+        let pem = std::fs::read(FIXTURE_CERT).expect("should have fixture");
+        let info = CertificateInfo::from_pem(&pem).expect("should describe cert");
+
+        assert!(info.subject.contains("Overkiz"));
+        assert_eq!(info.subject, info.issuer);
+        assert_eq!(info.sha256_fingerprint, OVERKIZ_ROOT_CA_SHA256);
+    }
+
+    #[test]
+    fn imports_inspects_and_clears_cache() {
+        let dir = cache_dir("certs_api");
+        assert_eq!(inspect(Some(&dir)).expect("should inspect"), None);
+
+        let imported = import_pem(FIXTURE_CERT, Some(&dir)).expect("should import cert");
+        let cached = inspect(Some(&dir)).expect("should inspect");
+        assert_eq!(cached, Some(imported));
+        assert_eq!(cache_location(Some(&dir)), Some(dir.join("cert.crt")));
+
+        assert!(clear(Some(&dir)).expect("should clear"));
+        assert!(!clear(Some(&dir)).expect("should clear"));
+        std::fs::remove_dir_all(&dir).expect("should not fail on cleanup");
+    }
+
+    #[test]
+    fn rejects_import_of_invalid_pem() {
+        let dir = cache_dir("certs_invalid");
+        let pem_path =
+            std::env::temp_dir().join(format!("somfy_sdk_{}_invalid.crt", std::process::id()));
+        std::fs::write(&pem_path, "not a cert").expect("should write file");
+
+        let result = import_pem(&pem_path, Some(&dir));
+        std::fs::remove_file(&pem_path).expect("should not fail on cleanup");
+        assert!(matches!(result, Err(CertificateError::Malformed(_))));
+        assert!(!dir.exists());
+
+        let result = import_pem("./tests/fixtures/cert/self-signed.crt", Some(&dir));
+        assert!(matches!(
+            result,
+            Err(CertificateError::FingerprintMismatch { .. })
+        ));
+        assert!(!dir.exists());
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn connects_with_imported_cert() {
+        use crate::api_client::{ApiClient, ApiClientConfig, CertificateHandling};
+
+        let dir = cache_dir("certs_connect");
+        import_pem(FIXTURE_CERT, Some(&dir)).expect("should import cert");
+        let cached = TlsCertHandler::cached_pem(cache_location(Some(&dir)).as_deref());
+
+        let config = ApiClientConfig::builder()
+            .gateway_pin("0000-1111-2222")
+            .api_key("my_key")
+            .cert_handling(CertificateHandling::DefaultCert(Some(
+                dir.to_string_lossy().to_string(),
+            )))
+            .build()
+            .expect("should build config");
+        let client = ApiClient::new(config).await;
+        std::fs::remove_dir_all(&dir).expect("should not fail on cleanup");
+
+        assert_eq!(cached, std::fs::read(FIXTURE_CERT).ok());
+        client.expect("should create client with the imported cert");
+    }
+}
@@ -1,10 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::CancelAllExecutionsResult;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -1,10 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::CancelExecutionResult;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 use urlencoding::encode;
 
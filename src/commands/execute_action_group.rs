@@ -15,17 +15,17 @@ impl crate::commands::traits::SomfyApiRequestCommand for ExecuteActionGroupComma
     ) -> Result<crate::commands::traits::RequestData, crate::err::http::RequestError> {
         let body_json = serde_json::to_string(&self.action_group)?;
 
-        let mut headers = reqwest::header::HeaderMap::new();
+        let mut headers = http::HeaderMap::new();
         headers.insert(
             "content-type",
-            reqwest::header::HeaderValue::from_str("application/json")
+            http::HeaderValue::from_str("application/json")
                 .map_err(|e| crate::err::http::RequestError::Server(e.into()))?,
         );
 
         Ok(crate::commands::traits::RequestData {
             path: "/enduser-mobile-web/1/enduserAPI/exec/apply".to_string(),
             method: crate::commands::traits::HttpMethod::POST,
-            body: reqwest::Body::from(body_json),
+            body: crate::commands::traits::Body::from(body_json),
             query_params: std::collections::HashMap::default(),
             header_map: headers,
         })
@@ -49,7 +49,7 @@ mod tests {
         let action_group = ActionGroup {
             label: Some("Test Action".to_string()),
             actions: vec![Action {
-                device_url: "io://test".to_string(),
+                device_url: "io://test/1".parse().expect("should parse"),
                 commands: vec![],
             }],
         };
@@ -68,11 +68,7 @@ mod tests {
             request.header_map.get("content-type").unwrap(),
             "application/json"
         );
-        assert!(!request
-            .body
-            .as_bytes()
-            .expect("should read body bytes")
-            .is_empty());
+        assert!(!request.body.as_bytes().is_empty());
     }
 
     #[test]
@@ -89,7 +85,7 @@ mod tests {
 
 #[cfg(feature = "generic-exec")]
 #[cfg(test)]
-mod execute_action_group {
+mod generic_exec_tests {
     use crate::commands::traits::{HttpMethod, SomfyApiRequestCommand};
 
     #[test]
@@ -99,7 +95,9 @@ mod execute_action_group {
         let execute_request = ActionGroup {
             label: Some("Test execution".to_string()),
             actions: vec![Action {
-                device_url: "io://0000-1111-2222/12345678".to_string(),
+                device_url: "io://0000-1111-2222/12345678"
+                    .parse()
+                    .expect("should parse"),
                 commands: vec![Command {
                     name: "open".to_string(),
                     parameters: vec![],
@@ -125,7 +123,9 @@ mod execute_action_group {
         let execute_request = ActionGroup {
             label: None,
             actions: vec![Action {
-                device_url: "io://0000-1111-2222/12345678".to_string(),
+                device_url: "io://0000-1111-2222/12345678"
+                    .parse()
+                    .expect("should parse"),
                 commands: vec![Command {
                     name: "close".to_string(),
                     parameters: vec![],
@@ -1,10 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::Event;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 use urlencoding::encode;
 
@@ -48,6 +47,29 @@ fn parse_valid_body_correctly() {
     assert_eq!(resp.len(), 2);
 }
 
+#[test]
+fn parses_state_and_execution_events() {
+    let body = r#"[
+      {
+        "name": "DeviceStateChangedEvent",
+        "deviceURL": "io://0812-2424-9999/1",
+        "deviceStates": [{"name": "core:ClosureState", "type": 1, "value": "50"}]
+      },
+      {
+        "name": "ExecutionStateChangedEvent",
+        "execId": "exec-1",
+        "oldState": "NOT_TRANSMITTED",
+        "newState": "IN_PROGRESS"
+      }
+    ]"#;
+    let resp = FetchEventsResponse::from_body(body).expect("should parse valid body correctly");
+
+    assert_eq!(resp[0].device_url.as_deref(), Some("io://0812-2424-9999/1"));
+    assert_eq!(resp[0].device_states[0].name, "core:ClosureState");
+    assert_eq!(resp[1].exec_id.as_deref(), Some("exec-1"));
+    assert_eq!(resp[1].new_state.as_deref(), Some("IN_PROGRESS"));
+}
+
 #[test]
 fn generates_correct_request_path() {
     let command = FetchEventsCommand {
@@ -1,10 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::ActionGroupExecution;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -51,7 +50,7 @@ fn parse_valid_body_correctly() {
               ]
             }
           ],
-          "deviceURL": "string"
+          "deviceURL": "io://0000-1111-2222/12345678"
         }
       ]
     },
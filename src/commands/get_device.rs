@@ -1,16 +1,16 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::Device;
+use crate::device_url::DeviceUrl;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 use urlencoding::encode;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct GetDeviceCommand<'a> {
-    pub device_url: &'a str,
+    pub device_url: &'a DeviceUrl,
 }
 
 impl SomfyApiRequestCommand for GetDeviceCommand<'_> {
@@ -43,7 +43,7 @@ mod tests {
     #[test]
     fn test_to_request() {
         let command = GetDeviceCommand {
-            device_url: "io://0812-2424-9999/246132",
+            device_url: &"io://0812-2424-9999/246132".parse().expect("should parse"),
         };
         let request = command
             .to_request()
@@ -56,11 +56,7 @@ mod tests {
         assert_eq!(request.method, HttpMethod::GET);
         assert!(request.query_params.is_empty());
         assert!(request.header_map.is_empty());
-        assert!(request
-            .body
-            .as_bytes()
-            .expect("should read body bytes")
-            .is_empty());
+        assert!(request.body.as_bytes().is_empty());
     }
 }
 
@@ -155,5 +151,17 @@ mod device_json_parser {
         }
     }
 
-    mod definition {}
+    mod definition {
+        use crate::commands::get_device::device_json_parser::load_json;
+        use crate::commands::types::DeviceDefinition;
+
+        #[test]
+        fn parse_valid_definition_type_correctly() {
+            let str = load_json("definition_valid_1.json");
+            let parsed: DeviceDefinition =
+                serde_json::from_str(&str).expect("should parse DeviceDefinition correctly");
+            assert_eq!(parsed.definition_type, "ACTUATOR");
+            assert_eq!(parsed.ui_class, "ExteriorVenetianBlind")
+        }
+    }
 }
@@ -1,16 +1,16 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::DeviceState;
+use crate::device_url::DeviceUrl;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 use urlencoding::encode;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct GetDeviceStateCommand<'a> {
-    pub device_url: &'a str,
+    pub device_url: &'a DeviceUrl,
     pub state_name: &'a str,
 }
 
@@ -58,7 +58,9 @@ fn parse_valid_body_correctly() {
 #[test]
 fn url_encoding_works_correctly() {
     let command = GetDeviceStateCommand {
-        device_url: "io://0000-1111-2222/12345678",
+        device_url: &"io://0000-1111-2222/12345678"
+            .parse()
+            .expect("should parse"),
         state_name: "core:StatusState",
     };
     let request_data = command.to_request().expect("should not err");
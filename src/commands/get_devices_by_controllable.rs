@@ -1,9 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
+use crate::device_url::DeviceUrl;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 use urlencoding::encode;
 
@@ -28,7 +28,7 @@ impl SomfyApiRequestCommand for GetDevicesByControllableCommand<'_> {
     }
 }
 
-pub type GetDevicesByControllableResponse = Vec<String>;
+pub type GetDevicesByControllableResponse = Vec<DeviceUrl>;
 
 impl SomfyApiRequestResponse for GetDevicesByControllableResponse {}
 
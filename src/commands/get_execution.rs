@@ -1,10 +1,10 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::ActionGroupExecution;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::{Body, StatusCode};
+use http::HeaderMap;
+use http::StatusCode;
 use std::collections::HashMap;
 use urlencoding::encode;
 
@@ -69,7 +69,7 @@ fn parse_valid_body_correctly() {
               ]
             }
           ],
-          "deviceURL": "string"
+          "deviceURL": "io://0000-1111-2222/12345678"
         }
       ]
     },
@@ -1,10 +1,9 @@
 use crate::commands::traits::{
-    HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
+    Body, HttpMethod, RequestData, SomfyApiRequestCommand, SomfyApiRequestResponse,
 };
 use crate::commands::types::Setup;
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::HeaderMap;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -42,11 +41,7 @@ mod tests {
         assert_eq!(request.method, HttpMethod::GET);
         assert!(request.query_params.is_empty());
         assert!(request.header_map.is_empty());
-        assert!(request
-            .body
-            .as_bytes()
-            .expect("should read body bytes")
-            .is_empty());
+        assert!(request.body.as_bytes().is_empty());
     }
 }
 
@@ -1,8 +1,8 @@
 use crate::err::http::RequestError;
-use reqwest::header::HeaderMap;
-use reqwest::Body;
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{HeaderMap, HeaderValue, Request, StatusCode};
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub enum HttpMethod {
@@ -12,7 +12,39 @@ pub enum HttpMethod {
     DELETE,
 }
 
-#[derive(Default)]
+/// The bytes of a request body.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Body(Vec<u8>);
+
+impl Body {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<String> for Body {
+    fn from(body: String) -> Self {
+        Self(body.into_bytes())
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(body: &'static str) -> Self {
+        Self(body.as_bytes().to_vec())
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct RequestData {
     pub header_map: HeaderMap,
     pub body: Body,
@@ -23,14 +55,60 @@ pub struct RequestData {
 
 impl RequestData {
     pub fn get_content_length(&self) -> String {
-        self.body.as_bytes().unwrap_or_default().len().to_string()
+        self.body.as_bytes().len().to_string()
+    }
+
+    /// The request as an `http::Request` for any HTTP client, authenticated with `api_key`.
+    /// `base_url` is the scheme, host and port of the gateway, e.g.
+    /// `https://gateway-0000-1111-2222.local:8443`.
+    pub fn into_http_request(
+        self,
+        base_url: &str,
+        api_key: &str,
+    ) -> Result<Request<Vec<u8>>, RequestError> {
+        let mut uri = format!("{}{}", base_url.trim_end_matches('/'), self.path);
+        // Sorted so the same request always has the same uri
+        let query: BTreeMap<_, _> = self.query_params.iter().collect();
+        for (i, (name, value)) in query.into_iter().enumerate() {
+            let separator = if i == 0 { '?' } else { '&' };
+            uri.push_str(&format!(
+                "{separator}{}={}",
+                urlencoding::encode(name),
+                urlencoding::encode(value)
+            ));
+        }
+
+        let content_length = self.body.as_bytes().len();
+        let method = match self.method {
+            HttpMethod::GET => http::Method::GET,
+            HttpMethod::POST => http::Method::POST,
+            HttpMethod::DELETE => http::Method::DELETE,
+        };
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(self.body.into_bytes())
+            .map_err(|e| RequestError::Server(e.into()))?;
+
+        let headers = request.headers_mut();
+        headers.extend(self.header_map);
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {api_key}"))
+                .map_err(|e| RequestError::Server(e.into()))?,
+        );
+        if self.method == HttpMethod::POST {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+        }
+        Ok(request)
     }
 
     pub fn default_post_headers() -> Result<HeaderMap, RequestError> {
         let mut headers = HeaderMap::new();
         headers.insert(
             "content-type",
-            reqwest::header::HeaderValue::from_str("application/json")
+            HeaderValue::from_str("application/json")
                 .map_err(|e| RequestError::Server(e.into()))?,
         );
 
@@ -41,6 +119,15 @@ impl RequestData {
 pub trait SomfyApiRequestCommand {
     type Response: SomfyApiRequestResponse;
     fn to_request(&self) -> Result<RequestData, RequestError>;
+
+    /// The command as an `http::Request`, see `RequestData::into_http_request`.
+    fn to_http_request(
+        &self,
+        base_url: &str,
+        api_key: &str,
+    ) -> Result<Request<Vec<u8>>, RequestError> {
+        self.to_request()?.into_http_request(base_url, api_key)
+    }
 }
 
 pub trait SomfyApiRequestResponse: DeserializeOwned {
@@ -50,6 +137,17 @@ pub trait SomfyApiRequestResponse: DeserializeOwned {
     {
         Ok(serde_json::from_str(body)?)
     }
+
+    /// Decodes a gateway response, non-2xx statuses are returned as errors.
+    fn from_response(status: StatusCode, body: &str) -> Result<Self, RequestError>
+    where
+        Self: DeserializeOwned,
+    {
+        if !status.is_success() {
+            return Err(RequestError::from_status(status, None));
+        }
+        Self::from_body(body)
+    }
 }
 
 #[cfg(test)]
@@ -64,11 +162,7 @@ mod tests {
         assert_eq!(request.path, "");
         assert!(request.query_params.is_empty());
         assert!(request.header_map.is_empty());
-        assert!(request
-            .body
-            .as_bytes()
-            .expect("should read body bytes")
-            .is_empty());
+        assert!(request.body.as_bytes().is_empty());
     }
 
     #[test]
@@ -86,12 +180,59 @@ mod tests {
     #[test]
     fn test_get_content_length_with_body() {
         let request = RequestData {
-            body: reqwest::Body::from("test body"),
+            body: Body::from("test body"),
             ..Default::default()
         };
         assert_eq!(request.get_content_length(), "9");
     }
 
+    #[test]
+    fn converts_commands_to_http_requests() {
+        use crate::commands::get_device::GetDeviceCommand;
+
+        let request = GetDeviceCommand {
+            device_url: &"io://0000-1111-2222/1".parse().expect("should parse"),
+        }
+        .to_http_request("https://gateway-0000-1111-2222.local:8443/", "my_key")
+        .expect("should convert command");
+        assert_eq!(request.method(), http::Method::GET);
+        assert_eq!(
+            request.uri(),
+            "https://gateway-0000-1111-2222.local:8443/enduser-mobile-web/1/enduserAPI/setup/devices/io%3A%2F%2F0000-1111-2222%2F1"
+        );
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer my_key");
+
+        let query = RequestData {
+            path: "/setup".to_string(),
+            method: HttpMethod::POST,
+            query_params: HashMap::from([
+                ("b".to_string(), "x y".to_string()),
+                ("a".to_string(), "1".to_string()),
+            ]),
+            body: Body::from("{}"),
+            ..Default::default()
+        }
+        .into_http_request("http://localhost:3000", "my_key")
+        .expect("should convert request");
+        assert_eq!(query.uri(), "http://localhost:3000/setup?a=1&b=x%20y");
+        assert_eq!(query.headers()[CONTENT_LENGTH], "2");
+        assert_eq!(query.body(), b"{}");
+    }
+
+    #[test]
+    fn decodes_responses() {
+        use crate::commands::get_version::GetVersionResponse;
+
+        let version =
+            GetVersionResponse::from_response(StatusCode::OK, r#"{"protocolVersion": "1"}"#)
+                .expect("should decode response");
+        assert_eq!(version.protocol_version, "1");
+        assert!(matches!(
+            GetVersionResponse::from_response(StatusCode::UNAUTHORIZED, ""),
+            Err(RequestError::Auth { .. })
+        ));
+    }
+
     #[test]
     fn test_default_post_headers() {
         let headers =
@@ -1,3 +1,4 @@
+use crate::device_url::DeviceUrl;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter, Result};
@@ -26,6 +27,22 @@ pub enum DeviceStateValue {
     Boolean(bool),
 }
 
+impl Display for DeviceStateValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            DeviceStateValue::String(s) => f.write_str(s),
+            DeviceStateValue::Int(i) => write!(f, "{i}"),
+            DeviceStateValue::Map(map) => {
+                let mut entries: Vec<_> = map.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                entries.sort();
+                f.write_str(entries.join(", ").as_str())
+            }
+            DeviceStateValue::Array(values) => f.write_str(values.join(", ").as_str()),
+            DeviceStateValue::Boolean(b) => write!(f, "{b}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceAttributeProcedureParams {
@@ -113,7 +130,7 @@ pub struct DeviceDefinition {
     pub ui_class: String,
     pub attributes: Vec<DeviceDefinitionAttribute>,
     #[serde(rename = "type")]
-    pub state_type: i64,
+    pub definition_type: String,
     pub states: Vec<DeviceDefinitionState>,
     pub commands: Vec<DeviceDefinitionCommand>,
     pub widget_name: String,
@@ -123,7 +140,7 @@ pub struct DeviceDefinition {
 #[serde(rename_all = "camelCase")]
 pub struct Device {
     #[serde(rename = "deviceURL")]
-    pub device_url: String,
+    pub device_url: DeviceUrl,
     pub label: String,
     pub controllable_name: String,
     pub subsystem_id: i64,
@@ -134,6 +151,45 @@ pub struct Device {
     pub enabled: bool,
     pub states: Vec<DeviceState>,
     pub attributes: Vec<DeviceAttribute>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub definition: Option<DeviceDefinition>,
+    #[serde(rename = "placeOID", default, skip_serializing_if = "Option::is_none")]
+    pub place_oid: Option<String>,
+}
+
+impl Device {
+    pub fn state(&self, name: &str) -> Option<&DeviceStateValue> {
+        self.states
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| &s.value)
+    }
+
+    /// Whether the device definition lists `command`. Devices without a definition support any command.
+    pub fn supports_command(&self, command: &str) -> bool {
+        self.definition
+            .as_ref()
+            .is_none_or(|d| d.commands.iter().any(|c| c.command_name == command))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Place {
+    pub oid: String,
+    pub label: String,
+    #[serde(default)]
+    pub sub_places: Vec<Place>,
+}
+
+impl Place {
+    /// Finds the place with `oid` in this place and all of its sub places.
+    pub fn find(&self, oid: &str) -> Option<&Place> {
+        if self.oid == oid {
+            return Some(self);
+        }
+        self.sub_places.iter().find_map(|p| p.find(oid))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -141,6 +197,8 @@ pub struct Device {
 pub struct Setup {
     pub gateways: Vec<Gateway>,
     pub devices: Vec<Device>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_place: Option<Place>,
 }
 
 impl Display for Device {
@@ -161,10 +219,32 @@ pub struct EventListener {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Event {
-    name: String,
-    protocol_type: Option<i64>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_type: Option<i64>,
+    #[serde(rename = "deviceURL", default, skip_serializing_if = "Option::is_none")]
+    pub device_url: Option<DeviceUrl>,
+    // Only set for DeviceStateChangedEvent
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device_states: Vec<DeviceState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_state: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_state: Option<String>,
+    // Only set for failed executions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
 }
 
+pub const DEVICE_STATE_CHANGED_EVENT: &str = "DeviceStateChangedEvent";
+pub const EXECUTION_STATE_CHANGED_EVENT: &str = "ExecutionStateChangedEvent";
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Command {
     pub name: String,
@@ -175,7 +255,7 @@ pub struct Command {
 #[serde(rename_all = "camelCase")]
 pub struct Action {
     #[serde(rename = "deviceURL")]
-    pub device_url: String,
+    pub device_url: DeviceUrl,
     pub commands: Vec<Command>,
 }
 
@@ -218,6 +298,21 @@ pub struct CancelExecutionResult {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_device_state_value_display() {
+        let map = HashMap::from([
+            ("b".to_string(), "2".to_string()),
+            ("a".to_string(), "1".to_string()),
+        ]);
+        assert_eq!(DeviceStateValue::Map(map).to_string(), "a=1, b=2");
+        assert_eq!(DeviceStateValue::Int(42).to_string(), "42");
+        assert_eq!(DeviceStateValue::Boolean(true).to_string(), "true");
+        assert_eq!(
+            DeviceStateValue::Array(vec!["x".to_string(), "y".to_string()]).to_string(),
+            "x, y"
+        );
+    }
+
     #[test]
     fn test_device_attribute_procedure_display() {
         let procedure = DeviceAttributeProcedure {
@@ -258,7 +353,7 @@ mod tests {
     #[test]
     fn test_device_display() {
         let device = Device {
-            device_url: "io://test-device".to_string(),
+            device_url: "io://test-device/1".parse().expect("should parse"),
             label: "Test Device".to_string(),
             controllable_name: "TestController".to_string(),
             subsystem_id: 1,
@@ -268,10 +363,57 @@ mod tests {
             enabled: true,
             states: vec![],
             attributes: vec![],
+            definition: None,
+            place_oid: None,
         };
         assert_eq!(
             device.to_string(),
-            "Test Device|io://test-device|TestController"
+            "Test Device|io://test-device/1|TestController"
         );
     }
+
+    #[test]
+    fn test_device_supports_command() {
+        let body = r#"{
+            "deviceURL": "io://0812-2424-9999/1",
+            "label": "Shutter",
+            "controllableName": "io:RollerShutterGenericIOComponent",
+            "subsystemId": 0,
+            "type": 1,
+            "available": true,
+            "synced": true,
+            "enabled": true,
+            "states": [{"name": "core:ClosureState", "type": 1, "value": 100}],
+            "attributes": [],
+            "placeOID": "room-1",
+            "definition": {
+                "uiClass": "RollerShutter",
+                "type": "ACTUATOR",
+                "widgetName": "PositionableRollerShutter",
+                "attributes": [],
+                "states": [],
+                "commands": [{"commandName": "open", "nparams": 0}]
+            }
+        }"#;
+        let device: Device = serde_json::from_str(body).expect("should parse device");
+
+        assert!(device.supports_command("open"));
+        assert!(!device.supports_command("my"));
+        assert_eq!(device.place_oid.as_deref(), Some("room-1"));
+        assert_eq!(
+            device.state("core:ClosureState"),
+            Some(&DeviceStateValue::Int(100))
+        );
+    }
+
+    #[test]
+    fn test_place_find() {
+        let body = r#"{"oid": "house", "label": "House", "subPlaces": [
+            {"oid": "floor", "label": "Floor", "subPlaces": [{"oid": "room", "label": "Room"}]}
+        ]}"#;
+        let place: Place = serde_json::from_str(body).expect("should parse place");
+
+        assert_eq!(place.find("room").map(|p| p.label.as_str()), Some("Room"));
+        assert_eq!(place.find("garage"), None);
+    }
 }
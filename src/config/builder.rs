@@ -0,0 +1,354 @@
+use crate::api_client::{ApiClientConfig, CertificateHandling, HttpProtocol, DEFAULT_PORT};
+use crate::config::pinning::is_valid_pin;
+use crate::err::config::ConfigError;
+use std::net::{IpAddr, Ipv6Addr};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiClientConfigBuilder {
+    host: Option<String>,
+    gateway_pin: Option<String>,
+    port: Option<u16>,
+    api_key: Option<String>,
+    protocol: Option<HttpProtocol>,
+    cert_handling: Option<CertificateHandling>,
+    tls_server_name: Option<String>,
+}
+
+impl ApiClientConfig {
+    /// Starts a validated config with defaults for HTTPS on port 8443 using `DefaultCert`.
+    pub fn builder() -> ApiClientConfigBuilder {
+        ApiClientConfigBuilder::default()
+    }
+}
+
+impl ApiClientConfigBuilder {
+    /// Host name or IP of the gateway, without scheme, port or path. IPv6 addresses may be
+    /// given with or without brackets.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Gateway PIN, used to derive the host `gateway-<pin>.local` if no host is set.
+    /// If the host is an IP address, the certificate is verified against `gateway-<pin>.local`.
+    pub fn gateway_pin(mut self, pin: impl Into<String>) -> Self {
+        self.gateway_pin = Some(pin.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: HttpProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn cert_handling(mut self, cert_handling: CertificateHandling) -> Self {
+        self.cert_handling = Some(cert_handling);
+        self
+    }
+
+    /// Host name to verify the certificate against, requires the host to be an IP address.
+    pub fn tls_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.tls_server_name = Some(server_name.into());
+        self
+    }
+
+    pub fn build(self) -> Result<ApiClientConfig, ConfigError> {
+        if let Some(pin) = &self.gateway_pin {
+            validate_gateway_pin(pin)?;
+        }
+
+        let pin_host = self.gateway_pin.map(|pin| format!("gateway-{pin}.local"));
+        let url = match (self.host, &pin_host) {
+            (Some(host), _) => {
+                validate_host(&host)?;
+                unbracket(&host).to_string()
+            }
+            (None, Some(pin_host)) => pin_host.clone(),
+            (None, None) => return Err(ConfigError::Missing("host or gateway_pin")),
+        };
+
+        let is_ip = url.parse::<IpAddr>().is_ok();
+        let tls_server_name = match self.tls_server_name {
+            Some(server_name) => {
+                validate_host(&server_name)?;
+                if !is_ip {
+                    return Err(ConfigError::InvalidHost {
+                        host: url,
+                        reason: "must be an IP address when tls_server_name is set",
+                    });
+                }
+                Some(server_name)
+            }
+            None if is_ip => pin_host,
+            None => None,
+        };
+
+        let port = self.port.unwrap_or(DEFAULT_PORT);
+        if port == 0 {
+            return Err(ConfigError::InvalidPort);
+        }
+
+        let api_key = match self.api_key {
+            Some(api_key) if !api_key.trim().is_empty() => api_key,
+            _ => return Err(ConfigError::Missing("api_key")),
+        };
+
+        let protocol = self.protocol.unwrap_or(HttpProtocol::HTTPS);
+        let cert_handling = self
+            .cert_handling
+            .unwrap_or(CertificateHandling::DefaultCert(None));
+        if protocol == HttpProtocol::HTTP && cert_handling != CertificateHandling::NoCustomCert {
+            return Err(ConfigError::CertWithoutHttps(format!("{cert_handling:?}")));
+        }
+        if let CertificateHandling::Pinned(pin) = &cert_handling {
+            if !is_valid_pin(pin) {
+                return Err(ConfigError::InvalidValue {
+                    name: "cert_handling.pinned",
+                    value: pin.to_string(),
+                });
+            }
+        }
+
+        Ok(ApiClientConfig {
+            cert_handling,
+            protocol,
+            url,
+            port,
+            api_key,
+            tls_server_name,
+        })
+    }
+}
+
+// `[::1]` to `::1`, other hosts are returned as is
+fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .filter(|h| h.parse::<Ipv6Addr>().is_ok())
+        .unwrap_or(host)
+}
+
+fn validate_host(host: &str) -> Result<(), ConfigError> {
+    let reason = if unbracket(host).parse::<Ipv6Addr>().is_ok() {
+        return Ok(());
+    } else if host.is_empty() {
+        "must not be empty"
+    } else if host.contains("://") {
+        "must not include a scheme"
+    } else if host.contains('/') {
+        "must not include a path"
+    } else if host.contains(':') {
+        "must not include a port, set it with port() instead"
+    } else if !host
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    {
+        "contains invalid characters"
+    } else {
+        return Ok(());
+    };
+
+    Err(ConfigError::InvalidHost {
+        host: host.to_string(),
+        reason,
+    })
+}
+
+fn validate_gateway_pin(pin: &str) -> Result<(), ConfigError> {
+    let groups: Vec<&str> = pin.split('-').collect();
+    let valid = groups.len() == 3
+        && groups
+            .iter()
+            .all(|g| g.len() == 4 && g.chars().all(|c| c.is_ascii_digit()));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::InvalidGatewayPin(pin.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_config_with_defaults() {
+        let config = ApiClientConfig::builder()
+            .gateway_pin("0000-1111-2222")
+            .api_key("my_key")
+            .build()
+            .expect("should build config");
+
+        assert_eq!(config.url, "gateway-0000-1111-2222.local");
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.api_key, "my_key");
+        assert_eq!(config.protocol, HttpProtocol::HTTPS);
+        assert_eq!(config.cert_handling, CertificateHandling::DefaultCert(None));
+    }
+
+    #[test]
+    fn host_takes_precedence_over_gateway_pin() {
+        let config = ApiClientConfig::builder()
+            .gateway_pin("0000-1111-2222")
+            .host("192.168.1.20")
+            .port(8080)
+            .api_key("my_key")
+            .build()
+            .expect("should build config");
+
+        assert_eq!(config.url, "192.168.1.20");
+        assert_eq!(config.port, 8080);
+    }
+
+    #[test]
+    fn derives_tls_server_name_for_ip_hosts_from_gateway_pin() {
+        let config = ApiClientConfig::builder()
+            .gateway_pin("0000-1111-2222")
+            .host("192.168.1.20")
+            .api_key("my_key")
+            .build()
+            .expect("should build config");
+        assert_eq!(
+            config.tls_server_name.as_deref(),
+            Some("gateway-0000-1111-2222.local")
+        );
+
+        let config = ApiClientConfig::builder()
+            .gateway_pin("0000-1111-2222")
+            .host("gateway.example.com")
+            .api_key("my_key")
+            .build()
+            .expect("should build config");
+        assert_eq!(config.tls_server_name, None);
+    }
+
+    #[test]
+    fn errs_for_tls_server_name_without_ip_host() {
+        let result = ApiClientConfig::builder()
+            .host("gateway.example.com")
+            .tls_server_name("gateway-0000-1111-2222.local")
+            .api_key("my_key")
+            .build();
+        assert!(matches!(result, Err(ConfigError::InvalidHost { .. })));
+    }
+
+    #[test]
+    fn errs_for_invalid_hosts() {
+        for host in [
+            "",
+            "https://gateway-0000-1111-2222.local",
+            "gateway-0000-1111-2222.local/api",
+            "gateway-0000-1111-2222.local:8443",
+            "gateway 0000",
+            "[gateway-0000-1111-2222.local]",
+            "[::1]:8443",
+        ] {
+            let result = ApiClientConfig::builder()
+                .host(host)
+                .api_key("my_key")
+                .build();
+            assert!(
+                matches!(result, Err(ConfigError::InvalidHost { .. })),
+                "{host:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_ipv6_hosts() {
+        for host in ["fe80::1", "[fe80::1]"] {
+            let config = ApiClientConfig::builder()
+                .gateway_pin("0000-1111-2222")
+                .host(host)
+                .api_key("my_key")
+                .build()
+                .expect("should build config");
+            assert_eq!(config.url, "fe80::1");
+            assert_eq!(
+                config.tls_server_name.as_deref(),
+                Some("gateway-0000-1111-2222.local")
+            );
+        }
+    }
+
+    #[test]
+    fn errs_for_invalid_gateway_pin() {
+        let result = ApiClientConfig::builder()
+            .gateway_pin("gateway-0000-1111-2222")
+            .api_key("my_key")
+            .build();
+        assert!(matches!(result, Err(ConfigError::InvalidGatewayPin(_))));
+    }
+
+    #[test]
+    fn errs_for_port_zero() {
+        let result = ApiClientConfig::builder()
+            .host("localhost")
+            .port(0)
+            .api_key("my_key")
+            .build();
+        assert!(matches!(result, Err(ConfigError::InvalidPort)));
+    }
+
+    #[test]
+    fn errs_for_missing_values() {
+        let result = ApiClientConfig::builder().api_key("my_key").build();
+        assert!(matches!(
+            result,
+            Err(ConfigError::Missing("host or gateway_pin"))
+        ));
+
+        let result = ApiClientConfig::builder()
+            .host("localhost")
+            .api_key(" ")
+            .build();
+        assert!(matches!(result, Err(ConfigError::Missing("api_key"))));
+    }
+
+    #[test]
+    fn errs_for_http_with_certificate() {
+        let result = ApiClientConfig::builder()
+            .host("localhost")
+            .api_key("my_key")
+            .protocol(HttpProtocol::HTTP)
+            .build();
+        assert!(matches!(result, Err(ConfigError::CertWithoutHttps(_))));
+
+        let config = ApiClientConfig::builder()
+            .host("localhost")
+            .api_key("my_key")
+            .protocol(HttpProtocol::HTTP)
+            .cert_handling(CertificateHandling::NoCustomCert)
+            .build()
+            .expect("should allow http without custom cert");
+        assert_eq!(config.protocol, HttpProtocol::HTTP);
+    }
+
+    #[test]
+    fn errs_for_invalid_pin() {
+        let result = ApiClientConfig::builder()
+            .host("192.168.1.20")
+            .api_key("my_key")
+            .cert_handling(CertificateHandling::Pinned("not-a-pin".to_string()))
+            .build();
+        assert!(matches!(result, Err(ConfigError::InvalidValue { .. })));
+
+        let config = ApiClientConfig::builder()
+            .host("192.168.1.20")
+            .api_key("my_key")
+            .cert_handling(CertificateHandling::Pinned("ab".repeat(32)))
+            .build();
+        assert!(config.is_ok());
+    }
+}
@@ -0,0 +1,355 @@
+use crate::api_client::{ApiClientConfig, CertificateHandling, HttpProtocol};
+use crate::err::config::ConfigError;
+use serde::Deserialize;
+use std::path::Path;
+
+pub const ENV_GATEWAY_PIN: &str = "SOMFY_GATEWAY_PIN";
+pub const ENV_API_KEY: &str = "SOMFY_API_KEY";
+pub const ENV_HOST: &str = "SOMFY_HOST";
+pub const ENV_PORT: &str = "SOMFY_PORT";
+pub const ENV_PROTOCOL: &str = "SOMFY_PROTOCOL";
+pub const ENV_CERT_PATH: &str = "SOMFY_CERT_PATH";
+pub const ENV_CERT_HANDLING: &str = "SOMFY_CERT_HANDLING";
+pub const ENV_CERT_PIN: &str = "SOMFY_CERT_PIN";
+pub const ENV_TLS_SERVER_NAME: &str = "SOMFY_TLS_SERVER_NAME";
+pub const ENV_CACHE_DIR: &str = "SOMFY_SDK_CACHE_DIR";
+
+/// A partial [`ApiClientConfig`] as read from a single source (file or environment).
+/// Layers are merged with [`ApiClientConfigLayer::merge`] and resolved with
+/// [`ApiClientConfigLayer::into_config`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiClientConfigLayer {
+    pub gateway_pin: Option<String>,
+    #[serde(alias = "url")]
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub api_key: Option<String>,
+    pub protocol: Option<HttpProtocol>,
+    pub cert_handling: Option<CertificateHandling>,
+    pub tls_server_name: Option<String>,
+}
+
+impl ApiClientConfigLayer {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    pub(crate) fn from_vars<F>(lookup: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let port = match lookup(ENV_PORT) {
+            Some(port) => Some(port.parse().map_err(|_| ConfigError::InvalidValue {
+                name: ENV_PORT,
+                value: port,
+            })?),
+            None => None,
+        };
+
+        let protocol = match lookup(ENV_PROTOCOL) {
+            Some(protocol) => Some(parse_protocol(&protocol)?),
+            None => None,
+        };
+
+        let cert_handling = match (
+            lookup(ENV_CERT_PIN),
+            lookup(ENV_CERT_PATH),
+            lookup(ENV_CERT_HANDLING),
+        ) {
+            (Some(pin), _, _) => Some(CertificateHandling::Pinned(pin)),
+            (None, Some(path), _) => Some(CertificateHandling::CertProvided(path)),
+            (None, None, Some(handling)) => Some(parse_cert_handling(&handling)?),
+            (None, None, None) => None,
+        };
+
+        Ok(Self {
+            gateway_pin: lookup(ENV_GATEWAY_PIN),
+            host: lookup(ENV_HOST),
+            port,
+            api_key: lookup(ENV_API_KEY),
+            protocol,
+            cert_handling,
+            tls_server_name: lookup(ENV_TLS_SERVER_NAME),
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(&content)?),
+            Some("json") => Ok(serde_json::from_str(&content)?),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Merges two layers, values set in `other` take precedence.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            gateway_pin: other.gateway_pin.or(self.gateway_pin),
+            host: other.host.or(self.host),
+            port: other.port.or(self.port),
+            api_key: other.api_key.or(self.api_key),
+            protocol: other.protocol.or(self.protocol),
+            cert_handling: other.cert_handling.or(self.cert_handling),
+            tls_server_name: other.tls_server_name.or(self.tls_server_name),
+        }
+    }
+
+    pub fn into_config(self) -> Result<ApiClientConfig, ConfigError> {
+        let mut builder = ApiClientConfig::builder();
+        if let Some(host) = self.host {
+            builder = builder.host(host);
+        }
+        if let Some(pin) = self.gateway_pin {
+            builder = builder.gateway_pin(pin);
+        }
+        if let Some(port) = self.port {
+            builder = builder.port(port);
+        }
+        if let Some(api_key) = self.api_key {
+            builder = builder.api_key(api_key);
+        }
+        if let Some(protocol) = self.protocol {
+            builder = builder.protocol(protocol);
+        }
+        if let Some(cert_handling) = self.cert_handling {
+            builder = builder.cert_handling(cert_handling);
+        }
+        if let Some(tls_server_name) = self.tls_server_name {
+            builder = builder.tls_server_name(tls_server_name);
+        }
+        builder.build()
+    }
+}
+
+impl ApiClientConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        ApiClientConfigLayer::from_env()?.into_config()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        ApiClientConfigLayer::from_file(path)?.into_config()
+    }
+
+    /// Reads the config file and applies environment overrides on top of it.
+    pub fn from_file_and_env(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        ApiClientConfigLayer::from_file(path)?
+            .merge(ApiClientConfigLayer::from_env()?)
+            .into_config()
+    }
+}
+
+fn parse_protocol(value: &str) -> Result<HttpProtocol, ConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "http" => Ok(HttpProtocol::HTTP),
+        "https" => Ok(HttpProtocol::HTTPS),
+        _ => Err(ConfigError::InvalidValue {
+            name: ENV_PROTOCOL,
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_cert_handling(value: &str) -> Result<CertificateHandling, ConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "default" | "default_cert" => Ok(CertificateHandling::DefaultCert(None)),
+        "embedded" => Ok(CertificateHandling::Embedded),
+        "none" | "no_custom_cert" => Ok(CertificateHandling::NoCustomCert),
+        _ => Err(ConfigError::InvalidValue {
+            name: ENV_CERT_HANDLING,
+            value: value.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::DEFAULT_PORT;
+    use std::collections::HashMap;
+
+    fn layer_from(vars: &[(&str, &str)]) -> Result<ApiClientConfigLayer, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ApiClientConfigLayer::from_vars(|name| vars.get(name).cloned())
+    }
+
+    fn write_temp_config(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("somfy_sdk_{}_{name}", std::process::id()));
+        std::fs::write(&path, content).expect("should write temp config");
+        path
+    }
+
+    #[test]
+    fn builds_config_from_env_with_defaults() {
+        let config = layer_from(&[(ENV_GATEWAY_PIN, "0000-1111-2222"), (ENV_API_KEY, "key")])
+            .expect("should read env")
+            .into_config()
+            .expect("should build config");
+
+        assert_eq!(config.url, "gateway-0000-1111-2222.local");
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.api_key, "key");
+        assert_eq!(config.protocol, HttpProtocol::HTTPS);
+        assert_eq!(config.cert_handling, CertificateHandling::DefaultCert(None));
+    }
+
+    #[test]
+    fn host_takes_precedence_over_gateway_pin() {
+        let config = layer_from(&[
+            (ENV_GATEWAY_PIN, "0000-1111-2222"),
+            (ENV_HOST, "192.168.1.20"),
+            (ENV_PORT, "8080"),
+            (ENV_PROTOCOL, "HTTPS"),
+            (ENV_CERT_PATH, "/etc/somfy/ca.crt"),
+            (ENV_API_KEY, "key"),
+        ])
+        .expect("should read env")
+        .into_config()
+        .expect("should build config");
+
+        assert_eq!(config.url, "192.168.1.20");
+        assert_eq!(
+            config.tls_server_name.as_deref(),
+            Some("gateway-0000-1111-2222.local")
+        );
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.protocol, HttpProtocol::HTTPS);
+        assert_eq!(
+            config.cert_handling,
+            CertificateHandling::CertProvided("/etc/somfy/ca.crt".to_string())
+        );
+    }
+
+    #[test]
+    fn errs_for_invalid_env_values() {
+        assert!(matches!(
+            layer_from(&[(ENV_PORT, "not-a-port")]),
+            Err(ConfigError::InvalidValue { name: ENV_PORT, .. })
+        ));
+        assert!(matches!(
+            layer_from(&[(ENV_PROTOCOL, "ftp")]),
+            Err(ConfigError::InvalidValue {
+                name: ENV_PROTOCOL,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn errs_for_missing_values() {
+        let err = layer_from(&[(ENV_API_KEY, "key")])
+            .expect("should read env")
+            .into_config();
+        assert!(matches!(err, Err(ConfigError::Missing(_))));
+
+        let err = layer_from(&[(ENV_HOST, "localhost")])
+            .expect("should read env")
+            .into_config();
+        assert!(matches!(err, Err(ConfigError::Missing("api_key"))));
+    }
+
+    #[test]
+    fn reads_toml_file() {
+        let path = write_temp_config(
+            "config.toml",
+            r#"
+            host = "gateway-0000-1111-2222.local"
+            port = 8443
+            api_key = "key"
+            protocol = "https"
+            cert_handling = { cert_provided = "/etc/somfy/ca.crt" }
+            "#,
+        );
+        let config = ApiClientConfig::from_file(&path).expect("should parse toml config");
+        std::fs::remove_file(&path).expect("should not fail on cleanup");
+
+        assert_eq!(config.url, "gateway-0000-1111-2222.local");
+        assert_eq!(
+            config.cert_handling,
+            CertificateHandling::CertProvided("/etc/somfy/ca.crt".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_json_file() {
+        let path = write_temp_config(
+            "config.json",
+            r#"{ "gateway_pin": "0000-1111-2222", "api_key": "key", "cert_handling": "no_custom_cert" }"#,
+        );
+        let config = ApiClientConfig::from_file(&path).expect("should parse json config");
+        std::fs::remove_file(&path).expect("should not fail on cleanup");
+
+        assert_eq!(config.url, "gateway-0000-1111-2222.local");
+        assert_eq!(config.cert_handling, CertificateHandling::NoCustomCert);
+    }
+
+    #[test]
+    fn never_writes_out_the_api_key() {
+        let config = layer_from(&[
+            (ENV_GATEWAY_PIN, "0000-1111-2222"),
+            (ENV_API_KEY, "secret-key"),
+        ])
+        .expect("should read env")
+        .into_config()
+        .expect("should build config");
+
+        let json = serde_json::to_string(&config).expect("should serialize");
+        assert!(!json.contains("secret-key") && !json.contains("api_key"));
+        assert!(!format!("{config:?}").contains("secret-key"));
+    }
+
+    #[test]
+    fn reads_default_cert_with_cache_dir() {
+        let layer: ApiClientConfigLayer =
+            toml::from_str(r#"cert_handling = { default_cert = "/var/cache/somfy" }"#)
+                .expect("should parse toml");
+        assert_eq!(
+            layer.cert_handling,
+            Some(CertificateHandling::DefaultCert(Some(
+                "/var/cache/somfy".to_string()
+            )))
+        );
+
+        let layer: ApiClientConfigLayer =
+            toml::from_str(r#"cert_handling = "default_cert""#).expect("should parse toml");
+        assert_eq!(
+            layer.cert_handling,
+            Some(CertificateHandling::DefaultCert(None))
+        );
+    }
+
+    #[test]
+    fn errs_for_unsupported_file_format() {
+        let path = write_temp_config("config.yaml", "api_key: key");
+        let err = ApiClientConfig::from_file(&path);
+        std::fs::remove_file(&path).expect("should not fail on cleanup");
+
+        assert!(matches!(err, Err(ConfigError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn env_layer_overrides_file_layer() {
+        let file = ApiClientConfigLayer {
+            host: Some("gateway-0000-1111-2222.local".to_string()),
+            port: Some(8443),
+            api_key: Some("file_key".to_string()),
+            ..Default::default()
+        };
+        let env = layer_from(&[(ENV_API_KEY, "env_key"), (ENV_CERT_HANDLING, "none")])
+            .expect("should read env");
+
+        let config = file.merge(env).into_config().expect("should build config");
+        assert_eq!(config.url, "gateway-0000-1111-2222.local");
+        assert_eq!(config.api_key, "env_key");
+        assert_eq!(config.cert_handling, CertificateHandling::NoCustomCert);
+    }
+}
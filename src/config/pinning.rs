@@ -0,0 +1,283 @@
+use crate::config::tls_cert::TlsCertHandler;
+use crate::err::cert::CertificateError;
+use log::debug;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+#[cfg(feature = "client")]
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use x509_parser::parse_x509_certificate;
+
+// Accepts exactly the server certificate whose SubjectPublicKeyInfo hashes to `expected`,
+// regardless of issuer and host name. Without an expected pin, any certificate is accepted
+// and its pin is recorded (trust on first use).
+#[derive(Debug)]
+pub(crate) struct PinnedCertVerifier {
+    expected: Option<String>,
+    observed: Arc<Mutex<Option<String>>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCertVerifier {
+    fn new(expected: Option<&str>) -> Self {
+        Self {
+            expected: expected.map(normalize_pin),
+            observed: Arc::new(Mutex::new(None)),
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = spki_sha256(end_entity).map_err(|_| {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+        })?;
+
+        if let Ok(mut observed) = self.observed.lock() {
+            *observed = Some(actual.clone());
+        }
+
+        match &self.expected {
+            Some(expected) if *expected != actual => {
+                debug!("Gateway cert pin {actual} does not match expected {expected}");
+                Err(rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// A rustls config accepting only the gateway certificate matching `pin`, for HTTP clients
+/// other than the one behind `ApiClient::new`.
+pub fn pinned_tls_config(pin: &str) -> Result<ClientConfig, CertificateError> {
+    if !is_valid_pin(pin) {
+        return Err(CertificateError::InvalidPin(pin.to_string()));
+    }
+    tls_config(Arc::new(PinnedCertVerifier::new(Some(pin))))
+}
+
+fn tls_config(verifier: Arc<PinnedCertVerifier>) -> Result<ClientConfig, CertificateError> {
+    Ok(
+        ClientConfig::builder_with_provider(verifier.provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(CertificateError::Tls)?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth(),
+    )
+}
+
+/// SHA-256 over the DER encoded SubjectPublicKeyInfo of a certificate, hex encoded.
+pub fn spki_sha256(cert_der: &[u8]) -> Result<String, CertificateError> {
+    let (_, cert) =
+        parse_x509_certificate(cert_der).map_err(|e| CertificateError::Malformed(e.to_string()))?;
+    Ok(TlsCertHandler::fingerprint(
+        cert.tbs_certificate.subject_pki.raw,
+    ))
+}
+
+/// Connects to the gateway, accepting whatever certificate it presents, and returns the
+/// SPKI pin of that certificate for use with `CertificateHandling::Pinned`.
+#[cfg(feature = "client")]
+pub async fn fetch_gateway_pin(host: &str, port: u16) -> Result<String, CertificateError> {
+    let verifier = Arc::new(PinnedCertVerifier::new(None));
+    let observed = verifier.observed.clone();
+    let client = reqwest::ClientBuilder::new()
+        .use_preconfigured_tls(tls_config(verifier)?)
+        .build()
+        .map_err(|e| CertificateError::GatewayUnreachable(e.to_string()))?;
+
+    // Any HTTP response will do, we only need the handshake to complete
+    let url = format!("https://{host}:{port}/enduser-mobile-web/1/enduserAPI/apiVersion");
+    if let Err(e) = client.get(&url).send().await {
+        debug!("Request to {url} failed after handshake: {e}");
+    }
+
+    let pin = observed.lock().ok().and_then(|pin| pin.clone());
+    pin.ok_or_else(|| CertificateError::GatewayUnreachable(url))
+}
+
+/// Returns the pin stored in `pin_file`, or fetches the gateway's pin and stores it there
+/// if the file does not exist yet.
+#[cfg(feature = "client")]
+pub async fn trust_on_first_use(
+    host: &str,
+    port: u16,
+    pin_file: impl AsRef<Path>,
+) -> Result<String, CertificateError> {
+    let pin_file = pin_file.as_ref();
+    match std::fs::read_to_string(pin_file) {
+        Ok(pin) if is_valid_pin(&pin) => return Ok(normalize_pin(&pin)),
+        Ok(pin) => {
+            return Err(CertificateError::InvalidPinFile {
+                path: pin_file.to_path_buf(),
+                pin: pin.trim().to_string(),
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(source) => {
+            return Err(CertificateError::Unreadable {
+                path: pin_file.to_path_buf(),
+                source,
+            })
+        }
+    }
+
+    let pin = fetch_gateway_pin(host, port).await?;
+    debug!("Recording gateway cert pin {pin} to {pin_file:?}");
+    TlsCertHandler::write_atomic(pin_file, pin.as_bytes())?;
+    Ok(pin)
+}
+
+pub(crate) fn normalize_pin(pin: &str) -> String {
+    pin.trim().replace(':', "").to_ascii_lowercase()
+}
+
+pub(crate) fn is_valid_pin(pin: &str) -> bool {
+    let pin = normalize_pin(pin);
+    pin.len() == 64 && pin.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x509_parser::pem::parse_x509_pem;
+
+    // SPKI pin of the Overkiz root CA fixture
+    const FIXTURE_SPKI_SHA256: &str =
+        "9a4ed18c3fcf9419ea40907400b386ac94d4f1729e360aa838cdda825ef06706";
+
+    fn fixture_der() -> Vec<u8> {
+        let pem = std::fs::read("./tests/fixtures/cert/overkiz-root-ca-2048.crt")
+            .expect("should have fixture");
+        let (_, pem) = parse_x509_pem(&pem).expect("should parse pem");
+        pem.contents
+    }
+
+    fn verify(verifier: &PinnedCertVerifier) -> Result<ServerCertVerified, rustls::Error> {
+        verifier.verify_server_cert(
+            &CertificateDer::from(fixture_der()),
+            &[],
+            &ServerName::try_from("gateway-0000-1111-2222.local").expect("valid name"),
+            &[],
+            UnixTime::now(),
+        )
+    }
+
+    #[test]
+    fn computes_spki_pin() {
+        let pin = spki_sha256(&fixture_der()).expect("should compute pin");
+        assert_eq!(pin, FIXTURE_SPKI_SHA256);
+        assert!(is_valid_pin(&pin));
+    }
+
+    #[test]
+    fn accepts_matching_pin() {
+        let verifier = PinnedCertVerifier::new(Some(&FIXTURE_SPKI_SHA256.to_ascii_uppercase()));
+        assert!(verify(&verifier).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_pin() {
+        let verifier = PinnedCertVerifier::new(Some(&"0".repeat(64)));
+        assert!(matches!(
+            verify(&verifier),
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure
+            ))
+        ));
+    }
+
+    #[test]
+    fn records_pin_without_expectation() {
+        let verifier = PinnedCertVerifier::new(None);
+        assert!(verify(&verifier).is_ok());
+        assert_eq!(
+            verifier.observed.lock().expect("should lock").as_deref(),
+            Some(FIXTURE_SPKI_SHA256)
+        );
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn reads_existing_pin_file() {
+        let path = std::env::temp_dir().join(format!("somfy_sdk_{}_pin", std::process::id()));
+        std::fs::write(&path, format!("{FIXTURE_SPKI_SHA256}\n")).expect("should write pin");
+
+        // The host is never contacted since the pin is already recorded
+        let pin = trust_on_first_use("unreachable.invalid", 8443, &path).await;
+        std::fs::remove_file(&path).expect("should not fail on cleanup");
+        assert_eq!(pin.expect("should read pin"), FIXTURE_SPKI_SHA256);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn rejects_corrupted_pin_file() {
+        let path = std::env::temp_dir().join(format!("somfy_sdk_{}_bad_pin", std::process::id()));
+        std::fs::write(&path, "not a pin\n").expect("should write pin");
+
+        let pin = trust_on_first_use("unreachable.invalid", 8443, &path).await;
+        std::fs::remove_file(&path).expect("should not fail on cleanup");
+        assert!(matches!(
+            pin,
+            Err(CertificateError::InvalidPinFile { pin, .. }) if pin == "not a pin"
+        ));
+    }
+
+    #[test]
+    fn validates_pin_format() {
+        assert!(is_valid_pin(&"AB:".repeat(32)));
+        assert!(!is_valid_pin("abc"));
+        assert!(!is_valid_pin(&"zz".repeat(32)));
+        assert!(matches!(
+            pinned_tls_config("abc"),
+            Err(CertificateError::InvalidPin(_))
+        ));
+    }
+}
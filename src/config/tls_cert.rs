@@ -1,77 +1,385 @@
+use crate::config::loader::ENV_CACHE_DIR;
 use crate::err::cert::CertificateError;
-use log::debug;
+#[cfg(feature = "client")]
+use log::{debug, warn};
+#[cfg(feature = "client")]
 use reqwest::Certificate;
-use std::fs::File;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use x509_parser::certificate::X509Certificate;
+use x509_parser::pem::parse_x509_pem;
+use x509_parser::time::ASN1Time;
 
 pub(crate) struct TlsCertHandler;
 
+#[cfg(feature = "embedded-cert")]
+const EMBEDDED_CERT: &[u8] = include_bytes!("../../certs/overkiz-root-ca-2048.crt");
+#[cfg(feature = "cert-download")]
 const REMOTE_CERT_LOCATION: &str = "https://ca.overkiz.com/overkiz-root-ca-2048.crt";
-const LOCAL_CERT_LOCATION_FOLDER: &str = ".somfy_sdk";
+// SHA-256 over the DER encoding of the Overkiz root CA
+#[cfg_attr(not(any(test, feature = "client")), allow(dead_code))]
+pub(crate) const OVERKIZ_ROOT_CA_SHA256: &str =
+    "bbfa96111eae15e3c6ed15f343276c0c2726032ea19df5bbd067e2f1cffc44db";
+const LEGACY_CERT_LOCATION_FOLDER: &str = ".somfy_sdk";
+const CACHE_CERT_LOCATION_FOLDER: &str = "somfy_sdk";
 const LOCAL_CERT_LOCATION_FILENAME: &str = "cert.crt";
 
 impl TlsCertHandler {
-    pub(crate) async fn ensure_local_certificate() -> Result<Certificate, CertificateError> {
-        let folder = Self::get_folder_location();
-        let path = Self::get_file_location();
+    // Resolution order: cached cert, embedded cert, download (opt-in via the `cert-download` feature)
+    #[cfg(feature = "client")]
+    pub(crate) async fn ensure_local_certificate(
+        cache_dir: Option<&Path>,
+    ) -> Result<Certificate, CertificateError> {
+        let folder = Self::get_folder_location(cache_dir);
+        let path = folder.as_ref().map(|f| Self::get_file_location(f));
 
-        Self::ensure_local_folder(folder)?;
-        if std::fs::read(&path).is_err() {
+        if let Some(cert) = Self::cached_pem(path.as_deref()) {
+            return Certificate::from_pem(&cert).map_err(|_| CertificateError::InvalidLocalCert);
+        }
+
+        #[cfg(feature = "embedded-cert")]
+        match Self::embedded_certificate() {
+            Ok(cert) => {
+                debug!("Cert not available at {path:?}. Using embedded cert");
+                return Ok(cert);
+            }
+            Err(e) => debug!("Embedded cert not usable: {e}"),
+        }
+
+        #[cfg(feature = "cert-download")]
+        {
             debug!("Cert not available at {path:?}. Downloading from {REMOTE_CERT_LOCATION}");
+            let (folder, path) = folder.zip(path).ok_or(CertificateError::NoCacheLocation)?;
+            Self::ensure_local_folder(folder)?;
             Self::download_cert(&path).await?;
+
+            let cert = std::fs::read(&path)
+                .map_err(|source| CertificateError::CacheUnreadable { path, source })?;
+            Certificate::from_pem(&cert).map_err(|_| CertificateError::InvalidLocalCert)
         }
 
-        let cert = std::fs::read(&path).map_err(|_| CertificateError::InvalidLocalCert)?;
-        Certificate::from_pem(&cert).map_err(|_| CertificateError::InvalidLocalCert)
+        #[cfg(not(feature = "cert-download"))]
+        {
+            debug!("Cert not available at {path:?}");
+            Err(CertificateError::NoCertAvailable)
+        }
     }
 
-    fn ensure_local_folder(folder: PathBuf) -> Result<(), CertificateError> {
+    // The cached cert at `path`, if there is one and it is the Overkiz root CA. An unreadable
+    // cache is not fatal, the next source is tried instead
+    #[cfg(feature = "client")]
+    pub(crate) fn cached_pem(path: Option<&Path>) -> Option<Vec<u8>> {
+        let path = path?;
+        let cert = match std::fs::read(path) {
+            Ok(cert) => cert,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Cached cert at {path:?} could not be read, ignoring it: {e}");
+                return None;
+            }
+        };
+        match Self::verify_pem(&cert, Some(OVERKIZ_ROOT_CA_SHA256)) {
+            Ok(()) => Some(cert),
+            Err(e) => {
+                debug!("Cached cert at {path:?} rejected: {e}");
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn embedded_certificate() -> Result<Certificate, CertificateError> {
+        #[cfg(feature = "embedded-cert")]
+        {
+            Self::verify_pem(EMBEDDED_CERT, Some(OVERKIZ_ROOT_CA_SHA256))?;
+            Certificate::from_pem(EMBEDDED_CERT).map_err(|_| CertificateError::InvalidLocalCert)
+        }
+
+        #[cfg(not(feature = "embedded-cert"))]
+        Err(CertificateError::NoCertAvailable)
+    }
+
+    // Checks that `pem` holds a currently valid X.509 certificate, optionally pinned to a
+    // SHA-256 fingerprint of its DER encoding
+    pub(crate) fn verify_pem(
+        pem: &[u8],
+        expected_fingerprint: Option<&str>,
+    ) -> Result<(), CertificateError> {
+        let (_, pem) =
+            parse_x509_pem(pem).map_err(|e| CertificateError::Malformed(e.to_string()))?;
+
+        if let Some(expected) = expected_fingerprint {
+            let actual = Self::fingerprint(&pem.contents);
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(CertificateError::FingerprintMismatch {
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+
+        let cert = pem
+            .parse_x509()
+            .map_err(|e| CertificateError::Malformed(e.to_string()))?;
+        Self::check_validity(&cert, ASN1Time::now())
+    }
+
+    fn check_validity(cert: &X509Certificate, now: ASN1Time) -> Result<(), CertificateError> {
+        let validity = cert.validity();
+        if now < validity.not_before {
+            return Err(CertificateError::NotYetValid {
+                not_before: Some(validity.not_before.to_string()),
+            });
+        }
+        if now > validity.not_after {
+            return Err(CertificateError::Expired {
+                not_after: Some(validity.not_after.to_string()),
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn fingerprint(der: &[u8]) -> String {
+        Sha256::digest(der)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    pub(crate) fn ensure_local_folder(folder: PathBuf) -> Result<(), CertificateError> {
         std::fs::create_dir_all(folder).map_err(|e| CertificateError::FileSystemError(e.into()))?;
         Ok(())
     }
 
-    async fn download_cert(path: &PathBuf) -> Result<(), CertificateError> {
+    #[cfg(feature = "cert-download")]
+    pub(crate) async fn download_cert(path: &Path) -> Result<(), CertificateError> {
         let resp = reqwest::get(REMOTE_CERT_LOCATION)
             .await
-            .map_err(|_| CertificateError::RemoteCertError)?;
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| CertificateError::DownloadFailed(e.into()))?;
         let body = resp
-            .text()
+            .bytes()
             .await
-            .map_err(|_| CertificateError::RemoteCertError)?;
-        let mut out =
-            File::create(path).map_err(|e| CertificateError::FileSystemError(e.into()))?;
-        std::io::copy(&mut body.as_bytes(), &mut out)
-            .map_err(|e| CertificateError::FileSystemError(e.into()))?;
+            .map_err(|e| CertificateError::DownloadFailed(e.into()))?;
 
-        Ok(())
+        Self::verify_pem(&body, Some(OVERKIZ_ROOT_CA_SHA256))?;
+        Self::write_atomic(path, &body)
+    }
+
+    // Writes to a sibling temp file first so readers never observe a partially written cert
+    pub(crate) fn write_atomic(path: &Path, content: &[u8]) -> Result<(), CertificateError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let write = || -> std::io::Result<()> {
+            let mut out = std::fs::File::create(&tmp_path)?;
+            out.write_all(content)?;
+            out.sync_all()?;
+            std::fs::rename(&tmp_path, path)
+        };
+
+        write().map_err(|e| {
+            let _ = std::fs::remove_file(&tmp_path);
+            CertificateError::FileSystemError(e.into())
+        })
     }
 
-    fn get_file_location() -> PathBuf {
-        let mut path = Self::get_folder_location();
-        path.push(LOCAL_CERT_LOCATION_FILENAME);
-        path
+    pub(crate) fn get_file_location(folder: &Path) -> PathBuf {
+        folder.join(LOCAL_CERT_LOCATION_FILENAME)
     }
 
-    fn get_folder_location() -> PathBuf {
-        let mut path = PathBuf::new();
-        let home = dirs::home_dir().unwrap_or(PathBuf::from("."));
-        path.push(home);
-        path.push(LOCAL_CERT_LOCATION_FOLDER);
+    // Resolution order: explicit dir, SOMFY_SDK_CACHE_DIR, existing $HOME/.somfy_sdk,
+    // XDG cache dir (e.g. $XDG_CACHE_HOME/somfy_sdk), $HOME/.somfy_sdk
+    pub(crate) fn get_folder_location(cache_dir: Option<&Path>) -> Option<PathBuf> {
+        Self::resolve_folder_location(
+            cache_dir,
+            std::env::var_os(ENV_CACHE_DIR).map(PathBuf::from),
+        )
+    }
 
-        path
+    fn resolve_folder_location(
+        cache_dir: Option<&Path>,
+        env_cache_dir: Option<PathBuf>,
+    ) -> Option<PathBuf> {
+        if let Some(dir) = cache_dir {
+            return Some(dir.to_path_buf());
+        }
+        if let Some(dir) = env_cache_dir.filter(|d| !d.as_os_str().is_empty()) {
+            return Some(dir);
+        }
+
+        let legacy = dirs::home_dir().map(|home| home.join(LEGACY_CERT_LOCATION_FOLDER));
+        if legacy.as_ref().is_some_and(|dir| dir.is_dir()) {
+            return legacy;
+        }
+
+        dirs::cache_dir()
+            .map(|dir| dir.join(CACHE_CERT_LOCATION_FOLDER))
+            .or(legacy)
     }
 }
 
 #[cfg(test)]
-#[tokio::test]
-async fn download_cert() {
-    let path = std::path::Path::new("./tests/fixtures/temp_cert.crt").to_path_buf();
-    println!("{path:?}");
-    let _ = TlsCertHandler::download_cert(&path).await;
-
-    let cert = std::fs::read(&path).expect("cert should have been downloaded");
-    let first_27_chars = str::from_utf8(&cert[..27]).expect("should be utf8 text");
-    assert_eq!(first_27_chars, "-----BEGIN CERTIFICATE-----");
-    assert_eq!(cert.len(), 1184);
-    std::fs::remove_file(&path).expect("should not fail on cleanup")
+mod tests {
+    use super::*;
+
+    const FIXTURE_CERT: &str = "./tests/fixtures/cert/overkiz-root-ca-2048.crt";
+
+    fn fixture() -> Vec<u8> {
+        std::fs::read(FIXTURE_CERT).expect("should have fixture")
+    }
+
+    #[cfg(feature = "embedded-cert")]
+    #[test]
+    fn embedded_cert_matches_overkiz_root_ca() {
+        assert_eq!(EMBEDDED_CERT, fixture().as_slice());
+        assert!(TlsCertHandler::embedded_certificate().is_ok());
+    }
+
+    #[cfg(all(feature = "client", not(feature = "embedded-cert")))]
+    #[test]
+    fn no_embedded_cert_without_feature() {
+        assert!(matches!(
+            TlsCertHandler::embedded_certificate(),
+            Err(CertificateError::NoCertAvailable)
+        ));
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn only_uses_cached_overkiz_root_ca() {
+        let cached = TlsCertHandler::cached_pem(Some(Path::new(FIXTURE_CERT)));
+        assert_eq!(cached, Some(fixture()));
+
+        // Valid, but not the Overkiz root CA
+        let other = Path::new("./tests/fixtures/cert/self-signed.crt");
+        assert_eq!(TlsCertHandler::cached_pem(Some(other)), None);
+        // Unreadable, a directory
+        let unreadable = Path::new("./tests/fixtures/cert");
+        assert_eq!(TlsCertHandler::cached_pem(Some(unreadable)), None);
+        assert_eq!(TlsCertHandler::cached_pem(None), None);
+    }
+
+    #[cfg(all(feature = "client", feature = "embedded-cert"))]
+    #[tokio::test]
+    async fn falls_back_to_embedded_cert_when_cache_is_unreadable() {
+        let folder =
+            std::env::temp_dir().join(format!("somfy_sdk_{}_unreadable", std::process::id()));
+        // A directory in place of the cert file
+        std::fs::create_dir_all(TlsCertHandler::get_file_location(&folder))
+            .expect("should create folder");
+
+        let cert = TlsCertHandler::ensure_local_certificate(Some(&folder)).await;
+        std::fs::remove_dir_all(&folder).expect("should clean up");
+        cert.expect("should fall back to the embedded cert");
+    }
+
+    #[test]
+    fn accepts_overkiz_root_ca() {
+        TlsCertHandler::verify_pem(&fixture(), Some(OVERKIZ_ROOT_CA_SHA256))
+            .expect("should accept the Overkiz root CA");
+    }
+
+    #[test]
+    fn rejects_fingerprint_mismatch() {
+        let err = TlsCertHandler::verify_pem(&fixture(), Some(&"0".repeat(64)));
+        assert!(matches!(
+            err,
+            Err(CertificateError::FingerprintMismatch { actual, .. }) if actual == OVERKIZ_ROOT_CA_SHA256
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_pem() {
+        let err = TlsCertHandler::verify_pem(b"<html>Not found</html>", None);
+        assert!(matches!(err, Err(CertificateError::Malformed(_))));
+    }
+
+    #[test]
+    fn rejects_certs_outside_validity_period() {
+        let pem = fixture();
+        let (_, pem) = parse_x509_pem(&pem).expect("should parse pem");
+        let cert = pem.parse_x509().expect("should parse cert");
+
+        // 2010-01-01 and 2040-01-01, the cert is valid from 2016 to 2036
+        let before = ASN1Time::from_timestamp(1262304000).expect("should be a valid time");
+        let after = ASN1Time::from_timestamp(2208988800).expect("should be a valid time");
+
+        assert!(matches!(
+            TlsCertHandler::check_validity(&cert, before),
+            Err(CertificateError::NotYetValid { .. })
+        ));
+        assert!(matches!(
+            TlsCertHandler::check_validity(&cert, after),
+            Err(CertificateError::Expired { .. })
+        ));
+    }
+
+    #[test]
+    fn writes_cert_atomically() {
+        let path = std::env::temp_dir().join(format!("somfy_sdk_{}_cert.crt", std::process::id()));
+        TlsCertHandler::write_atomic(&path, &fixture()).expect("should write cert");
+
+        assert_eq!(std::fs::read(&path).expect("should read cert"), fixture());
+        let leftovers = std::fs::read_dir(std::env::temp_dir())
+            .expect("should list temp dir")
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .filter(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .starts_with(&format!("somfy_sdk_{}_cert", std::process::id()))
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+        std::fs::remove_file(&path).expect("should not fail on cleanup")
+    }
+
+    #[test]
+    fn explicit_cache_dir_takes_precedence() {
+        let folder = TlsCertHandler::resolve_folder_location(
+            Some(Path::new("/var/cache/explicit")),
+            Some(PathBuf::from("/var/cache/env")),
+        );
+        assert_eq!(folder, Some(PathBuf::from("/var/cache/explicit")));
+    }
+
+    #[test]
+    fn env_cache_dir_takes_precedence_over_defaults() {
+        let folder =
+            TlsCertHandler::resolve_folder_location(None, Some(PathBuf::from("/var/cache/env")));
+        assert_eq!(folder, Some(PathBuf::from("/var/cache/env")));
+
+        let folder = TlsCertHandler::resolve_folder_location(None, Some(PathBuf::new()));
+        assert_ne!(folder, Some(PathBuf::new()));
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn reads_cert_from_configured_cache_dir() {
+        let folder = std::env::temp_dir().join(format!("somfy_sdk_{}_cache", std::process::id()));
+        std::fs::create_dir_all(&folder).expect("should create cache dir");
+        TlsCertHandler::write_atomic(&TlsCertHandler::get_file_location(&folder), &fixture())
+            .expect("should write cert");
+
+        let cert = TlsCertHandler::ensure_local_certificate(Some(&folder)).await;
+        std::fs::remove_dir_all(&folder).expect("should not fail on cleanup");
+        assert!(cert.is_ok());
+    }
+
+    #[cfg(feature = "cert-download")]
+    #[tokio::test]
+    async fn download_cert() {
+        let path = std::path::Path::new("./tests/fixtures/temp_cert.crt").to_path_buf();
+        println!("{path:?}");
+        let _ = TlsCertHandler::download_cert(&path).await;
+
+        let cert = std::fs::read(&path).expect("cert should have been downloaded");
+        let first_27_chars = str::from_utf8(&cert[..27]).expect("should be utf8 text");
+        assert_eq!(first_27_chars, "-----BEGIN CERTIFICATE-----");
+        assert_eq!(cert.len(), 1184);
+        std::fs::remove_file(&path).expect("should not fail on cleanup")
+    }
 }
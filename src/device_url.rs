@@ -0,0 +1,288 @@
+use crate::err::device_url::DeviceUrlError;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Io,
+    Rts,
+    Zigbee,
+    Internal,
+    Ogp,
+    Other(String),
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Protocol::Io => "io",
+            Protocol::Rts => "rts",
+            Protocol::Zigbee => "zigbee",
+            Protocol::Internal => "internal",
+            Protocol::Ogp => "ogp",
+            Protocol::Other(protocol) => protocol,
+        }
+    }
+}
+
+impl From<&str> for Protocol {
+    fn from(value: &str) -> Self {
+        match value {
+            "io" => Protocol::Io,
+            "rts" => Protocol::Rts,
+            "zigbee" => Protocol::Zigbee,
+            "internal" => Protocol::Internal,
+            "ogp" => Protocol::Ogp,
+            other => Protocol::Other(other.to_string()),
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A device URL of the form `protocol://gateway-id/address[#subsystem]`, e.g.
+/// `io://0000-1111-2222/12345678#1`. Serialized as the plain URL, and usable wherever commands
+/// take a `&str`.
+///
+/// `parse` rejects URLs of any other shape. Deserializing keeps them as they are, so a device
+/// the parser does not know does not fail a whole response, its parts are just `None`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct DeviceUrl {
+    url: String,
+    parts: Option<Parts>,
+}
+
+// Byte offsets of the gateway id, the address and the subsystem separator in the URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Parts {
+    gateway_start: usize,
+    address_start: usize,
+    subsystem_start: Option<usize>,
+}
+
+impl DeviceUrl {
+    pub fn parse(url: &str) -> Result<Self, DeviceUrlError> {
+        let invalid = |reason| DeviceUrlError {
+            url: url.to_string(),
+            reason,
+        };
+
+        let (protocol, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid("missing protocol"))?;
+        if protocol.is_empty() {
+            return Err(invalid("missing protocol"));
+        }
+        let gateway_start = protocol.len() + "://".len();
+        let (gateway_id, address) = rest
+            .split_once('/')
+            .ok_or_else(|| invalid("missing address"))?;
+        if gateway_id.is_empty() {
+            return Err(invalid("missing gateway id"));
+        }
+        let address_start = gateway_start + gateway_id.len() + 1;
+
+        let (base_address, subsystem) = match address.split_once('#') {
+            Some((base, subsystem)) => (base, Some(subsystem)),
+            None => (address, None),
+        };
+        if base_address.is_empty() {
+            return Err(invalid("missing address"));
+        }
+        if subsystem.is_some_and(|s| s.parse::<u32>().is_err()) {
+            return Err(invalid("subsystem is not a number"));
+        }
+
+        Ok(Self {
+            url: url.to_string(),
+            parts: Some(Parts {
+                gateway_start,
+                address_start,
+                subsystem_start: subsystem.map(|_| address_start + base_address.len()),
+            }),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// Whether the URL has the expected shape, always true for URLs from `parse`.
+    pub fn is_valid(&self) -> bool {
+        self.parts.is_some()
+    }
+
+    pub fn protocol(&self) -> Option<Protocol> {
+        let parts = self.parts?;
+        Some(Protocol::from(
+            &self.url[..parts.gateway_start - "://".len()],
+        ))
+    }
+
+    /// The PIN of the gateway the device is paired with, e.g. `0000-1111-2222`.
+    pub fn gateway_id(&self) -> Option<&str> {
+        let parts = self.parts?;
+        Some(&self.url[parts.gateway_start..parts.address_start - 1])
+    }
+
+    /// The address without the subsystem, the same for all subsystems of a device.
+    pub fn base_address(&self) -> Option<&str> {
+        let parts = self.parts?;
+        let end = parts.subsystem_start.unwrap_or(self.url.len());
+        Some(&self.url[parts.address_start..end])
+    }
+
+    pub fn subsystem(&self) -> Option<u32> {
+        self.parts?
+            .subsystem_start
+            .and_then(|start| self.url[start + 1..].parse().ok())
+    }
+}
+
+impl FromStr for DeviceUrl {
+    type Err = DeviceUrlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+// Lenient, used when deserializing gateway responses
+impl From<String> for DeviceUrl {
+    fn from(value: String) -> Self {
+        Self::parse(&value).unwrap_or(Self {
+            url: value,
+            parts: None,
+        })
+    }
+}
+
+impl From<DeviceUrl> for String {
+    fn from(value: DeviceUrl) -> Self {
+        value.url
+    }
+}
+
+impl Deref for DeviceUrl {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.url
+    }
+}
+
+// Hashes like the plain URL, so maps keyed by `DeviceUrl` can be queried with a `&str`
+impl Hash for DeviceUrl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.url.hash(state)
+    }
+}
+
+impl Borrow<str> for DeviceUrl {
+    fn borrow(&self) -> &str {
+        &self.url
+    }
+}
+
+impl PartialEq<str> for DeviceUrl {
+    fn eq(&self, other: &str) -> bool {
+        self.url == other
+    }
+}
+
+impl PartialEq<&str> for DeviceUrl {
+    fn eq(&self, other: &&str) -> bool {
+        self.url == *other
+    }
+}
+
+impl PartialEq<String> for DeviceUrl {
+    fn eq(&self, other: &String) -> bool {
+        &self.url == other
+    }
+}
+
+impl AsRef<str> for DeviceUrl {
+    fn as_ref(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Display for DeviceUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::get_device::GetDeviceCommand;
+
+    #[test]
+    fn parses_device_urls() {
+        let url = DeviceUrl::parse("io://0000-1111-2222/12345678#1").expect("should parse");
+        assert_eq!(url.protocol(), Some(Protocol::Io));
+        assert_eq!(url.gateway_id(), Some("0000-1111-2222"));
+        assert_eq!(url.base_address(), Some("12345678"));
+        assert_eq!(url.subsystem(), Some(1));
+
+        let url: DeviceUrl = "internal://0000-1111-2222/pod/0"
+            .parse()
+            .expect("should parse");
+        assert_eq!(url.protocol(), Some(Protocol::Internal));
+        assert_eq!(url.base_address(), Some("pod/0"));
+        assert_eq!(url.subsystem(), None);
+        assert_eq!(
+            DeviceUrl::parse("hue://0000-1111-2222/1").map(|u| u.protocol()),
+            Ok(Some(Protocol::Other("hue".to_string())))
+        );
+
+        for invalid in [
+            "0000-1111-2222/1",
+            "://0000-1111-2222/1",
+            "io://0000-1111-2222",
+            "io:///1",
+            "io://0000-1111-2222/#1",
+            "io://0000-1111-2222/1#a",
+        ] {
+            assert!(
+                DeviceUrl::parse(invalid).is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn serializes_as_plain_string() {
+        let url = DeviceUrl::parse("rts://0000-1111-2222/16756006").expect("should parse");
+        let json = serde_json::to_string(&url).expect("should serialize");
+        assert_eq!(json, r#""rts://0000-1111-2222/16756006""#);
+        assert_eq!(
+            serde_json::from_str::<DeviceUrl>(&json).expect("should deserialize"),
+            url
+        );
+
+        let unknown: DeviceUrl =
+            serde_json::from_str(r#""not a device url""#).expect("should deserialize");
+        assert!(!unknown.is_valid());
+        assert_eq!(unknown, "not a device url");
+        assert_eq!(unknown.gateway_id(), None);
+        assert_eq!(
+            serde_json::to_string(&unknown).expect("should serialize"),
+            r#""not a device url""#
+        );
+
+        let command = GetDeviceCommand { device_url: &url };
+        assert_eq!(command.device_url, "rts://0000-1111-2222/16756006");
+    }
+}
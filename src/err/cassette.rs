@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CassetteError {
+    #[error("cassette file {path:?} could not be accessed: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid cassette: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unsupported cassette version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("no recorded response left for {method} {path}")]
+    NoMatch { method: String, path: String },
+}
@@ -1,11 +1,99 @@
+use rustls::pki_types::UnixTime;
+use std::path::PathBuf;
 use thiserror::Error;
+use x509_parser::time::ASN1Time;
 
 #[derive(Debug, Error)]
 pub enum CertificateError {
-    #[error("remote cert could not be retrieved")]
-    RemoteCertError,
+    #[error("remote cert could not be downloaded: {0}")]
+    DownloadFailed(#[source] Box<dyn std::error::Error + Send + Sync>),
     #[error("local cert is invalid")]
     InvalidLocalCert,
+    #[error("cert at {path:?} could not be read: {source}")]
+    Unreadable {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("cached cert at {path:?} could not be read: {source}")]
+    CacheUnreadable {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("no certificate available, enable the embedded-cert or cert-download feature")]
+    NoCertAvailable,
+    #[error("no cert cache location available, set SOMFY_SDK_CACHE_DIR")]
+    NoCacheLocation,
+    #[error("cert is not a valid PEM encoded X.509 certificate: {0}")]
+    Malformed(String),
+    #[error("cert fingerprint {actual} does not match expected {expected}")]
+    FingerprintMismatch { expected: String, actual: String },
+    #[error("cert expired{}", suffix(" at ", not_after))]
+    Expired { not_after: Option<String> },
+    #[error("cert is not valid yet{}", suffix(", not before ", not_before))]
+    NotYetValid { not_before: Option<String> },
+    #[error("server cert is not issued by a trusted CA, check the configured certificate")]
+    UnknownIssuer,
+    #[error("server cert is not valid for the requested host name{}", suffix(": ", .0))]
+    HostnameMismatch(Option<String>),
+    #[error("server cert does not match the pinned public key")]
+    PinMismatch,
+    #[error("invalid pin {0:?}, expected the hex encoded SHA-256 of a public key")]
+    InvalidPin(String),
+    #[error("pin file {path:?} holds an invalid pin {pin:?}, delete it to record the pin again")]
+    InvalidPinFile { path: PathBuf, pin: String },
+    #[error("server cert rejected: {0}")]
+    Rejected(String),
+    #[error("gateway certificate could not be retrieved from {0}")]
+    GatewayUnreachable(String),
+    #[error("tls error: {0}")]
+    Tls(#[source] rustls::Error),
     #[error("fs error: {0}")]
     FileSystemError(#[from] anyhow::Error),
 }
+
+impl From<&rustls::Error> for CertificateError {
+    fn from(e: &rustls::Error) -> Self {
+        use rustls::CertificateError as Kind;
+
+        let rustls::Error::InvalidCertificate(kind) = e else {
+            return CertificateError::Tls(e.clone());
+        };
+        match kind {
+            Kind::UnknownIssuer => CertificateError::UnknownIssuer,
+            Kind::Expired => CertificateError::Expired { not_after: None },
+            Kind::ExpiredContext { not_after, .. } => CertificateError::Expired {
+                not_after: Some(format_time(not_after)),
+            },
+            Kind::NotValidYet => CertificateError::NotYetValid { not_before: None },
+            Kind::NotValidYetContext { not_before, .. } => CertificateError::NotYetValid {
+                not_before: Some(format_time(not_before)),
+            },
+            Kind::NotValidForName => CertificateError::HostnameMismatch(None),
+            Kind::NotValidForNameContext {
+                expected,
+                presented,
+            } => CertificateError::HostnameMismatch(Some(format!(
+                "expected {expected:?}, cert is valid for {presented:?}"
+            ))),
+            // Only raised by the pinned verifier
+            Kind::ApplicationVerificationFailure => CertificateError::PinMismatch,
+            other => CertificateError::Rejected(format!("{other:?}")),
+        }
+    }
+}
+
+// Details that are not always known, e.g. " at <time>", or nothing
+fn suffix(separator: &str, detail: &Option<String>) -> String {
+    detail
+        .as_ref()
+        .map(|d| format!("{separator}{d}"))
+        .unwrap_or_default()
+}
+
+fn format_time(time: &UnixTime) -> String {
+    ASN1Time::from_timestamp(time.as_secs() as i64)
+        .map(|t| t.to_string())
+        .unwrap_or_else(|_| format!("{} (unix time)", time.as_secs()))
+}
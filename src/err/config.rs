@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("config file {path:?} could not be read: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("unsupported config file format for {0:?}, expected .toml or .json")]
+    UnsupportedFormat(PathBuf),
+
+    #[error("invalid toml config: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("invalid json config: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid value {value:?} for {name}")]
+    InvalidValue { name: &'static str, value: String },
+
+    #[error("missing config value: {0}")]
+    Missing(&'static str),
+
+    #[error("invalid host {host:?}: {reason}")]
+    InvalidHost { host: String, reason: &'static str },
+
+    #[error("invalid gateway pin {0:?}, expected format 0000-1111-2222")]
+    InvalidGatewayPin(String),
+
+    #[error("port must not be 0")]
+    InvalidPort,
+
+    #[error("certificate handling {0} requires https")]
+    CertWithoutHttps(String),
+}
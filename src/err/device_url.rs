@@ -0,0 +1,8 @@
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("invalid device url {url:?}: {reason}")]
+pub struct DeviceUrlError {
+    pub url: String,
+    pub reason: &'static str,
+}
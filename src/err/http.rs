@@ -1,9 +1,15 @@
-use reqwest::{Error as ReqwestError, StatusCode};
+use crate::err::cert::CertificateError;
+use http::StatusCode;
 use serde_json::Error as SerdeError;
+#[cfg(feature = "client")]
 use std::error::Error;
 use std::option::Option;
 use thiserror::Error;
 
+/// Error of the HTTP client sending the request, e.g. a `reqwest::Error` behind `ApiClient::new`.
+/// Boxed so the variants are the same with and without the `client` feature.
+pub type HttpClientError = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Debug, Error)]
 #[error("server error occurred: {0}")]
 pub struct CustomServerError(pub String);
@@ -31,7 +37,7 @@ impl From<CustomServerError> for RequestError {
 pub enum RequestError {
     /// Network / protocol problems (DNS, TLS, timeouts, malformed HTTP…)
     #[error("transport error")]
-    Transport(#[source] ReqwestError),
+    Transport(#[source] HttpClientError),
 
     /// Non-2xx from the server
     #[error("http status {status}")]
@@ -39,7 +45,7 @@ pub enum RequestError {
         status: StatusCode,
         // keep the original for extra context if you want
         #[source]
-        source: Option<reqwest::Error>,
+        source: Option<HttpClientError>,
     },
 
     /// Authentication/authorization problems
@@ -48,16 +54,16 @@ pub enum RequestError {
         message: String,
         status: Option<StatusCode>,
         #[source]
-        source: Option<reqwest::Error>, // e.g., 401/403 response or JWT parse error’s source
+        source: Option<HttpClientError>, // e.g., 401/403 response or JWT parse error’s source
     },
 
     /// Body could not be serialized/deserialized
     #[error("invalid body")]
     Body(#[source] serde_json::Error),
 
-    /// Certificate problems (useful in your project)
-    #[error("tls certificate rejected")]
-    Cert,
+    /// Certificate problems, carrying why the cert could not be loaded or was rejected
+    #[error("tls certificate rejected: {0}")]
+    Cert(#[source] CertificateError),
 
     /// Any other server error
     #[error(transparent)]
@@ -70,40 +76,62 @@ impl From<SerdeError> for RequestError {
     }
 }
 
-impl From<ReqwestError> for RequestError {
-    fn from(e: ReqwestError) -> Self {
+#[cfg(feature = "client")]
+impl From<reqwest::Error> for RequestError {
+    fn from(e: reqwest::Error) -> Self {
         match e.status() {
-            Some(code) if [StatusCode::FORBIDDEN, StatusCode::UNAUTHORIZED].contains(&code) => {
-                RequestError::Auth {
-                    message: "auth failed".to_string(),
-                    status: Some(code),
-                    source: Some(e),
-                }
-            }
-            Some(other_code) => RequestError::Status {
-                status: other_code,
-                source: Some(e),
-            },
+            Some(code) => RequestError::from_status(code, Some(e.into())),
             None => {
                 // Since certs are self-signed this will be a common source of error,
                 // so we treat certificate errors explicitly.
-
-                let mut source = e.source();
-                while let Some(e) = source {
-                    let err_str = e.to_string().to_ascii_lowercase();
-                    if err_str.contains("certificate")
-                        || err_str.contains("tls")
-                        || err_str.contains("ssl")
-                    {
-                        return RequestError::Cert;
-                    }
-                    source = e.source()
+                match find_rustls_error(&e) {
+                    Some(tls_error) => RequestError::Cert(tls_error.into()),
+                    None => RequestError::Transport(e.into()),
                 }
+            }
+        }
+    }
+}
 
-                RequestError::Transport(e)
+impl RequestError {
+    /// Error for a non-2xx `status`, with the client error if the client reported the status.
+    pub(crate) fn from_status(status: StatusCode, source: Option<HttpClientError>) -> Self {
+        if [StatusCode::FORBIDDEN, StatusCode::UNAUTHORIZED].contains(&status) {
+            RequestError::Auth {
+                message: "auth failed".to_string(),
+                status: Some(status),
+                source,
             }
+        } else {
+            RequestError::Status { status, source }
+        }
+    }
+}
+
+impl From<CertificateError> for RequestError {
+    fn from(e: CertificateError) -> Self {
+        RequestError::Cert(e)
+    }
+}
+
+// rustls errors reach us wrapped in an io::Error, whose `source()` skips the wrapped error
+#[cfg(feature = "client")]
+fn find_rustls_error<'a>(e: &'a (dyn Error + 'static)) -> Option<&'a rustls::Error> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if let Some(tls_error) = e.downcast_ref::<rustls::Error>() {
+            return Some(tls_error);
         }
+        let inner = e
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>());
+        if inner.is_some() {
+            return inner;
+        }
+        source = e.source();
     }
+    None
 }
 
 #[cfg(test)]
@@ -157,4 +185,61 @@ mod tests {
             "error in request / response mapping"
         );
     }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn finds_rustls_error_wrapped_in_io_error() {
+        let tls_error = rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer);
+        let io_error = std::io::Error::other(tls_error);
+
+        let found = find_rustls_error(&io_error).expect("should find rustls error");
+        assert!(matches!(
+            CertificateError::from(found),
+            CertificateError::UnknownIssuer
+        ));
+    }
+
+    #[test]
+    fn maps_rustls_certificate_errors() {
+        let cases = [
+            (
+                rustls::CertificateError::ApplicationVerificationFailure,
+                "server cert does not match the pinned public key",
+            ),
+            (
+                rustls::CertificateError::NotValidForName,
+                "server cert is not valid for the requested host name",
+            ),
+            (
+                rustls::CertificateError::NotValidYet,
+                "cert is not valid yet",
+            ),
+            (
+                rustls::CertificateError::ExpiredContext {
+                    time: rustls::pki_types::UnixTime::since_unix_epoch(
+                        std::time::Duration::from_secs(1_800_000_000),
+                    ),
+                    not_after: rustls::pki_types::UnixTime::since_unix_epoch(
+                        std::time::Duration::from_secs(1_700_000_000),
+                    ),
+                },
+                "cert expired at Nov 14 22:13:20 2023 +00:00",
+            ),
+        ];
+
+        for (kind, message) in cases {
+            let error = CertificateError::from(&rustls::Error::InvalidCertificate(kind));
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn cert_error_keeps_cause() {
+        let request_error = RequestError::from(CertificateError::NoCertAvailable);
+        assert!(matches!(
+            request_error,
+            RequestError::Cert(CertificateError::NoCertAvailable)
+        ));
+        assert!(std::error::Error::source(&request_error).is_some());
+    }
 }
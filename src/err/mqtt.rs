@@ -0,0 +1,17 @@
+use crate::err::http::RequestError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MqttBridgeError {
+    #[error("gateway request failed: {0}")]
+    Request(#[from] RequestError),
+
+    #[error("mqtt client error: {0}")]
+    Client(#[from] rumqttc::ClientError),
+
+    #[error("invalid command payload {payload:?} on {topic}")]
+    InvalidCommand { topic: String, payload: String },
+
+    #[error("unknown device for command topic {0}")]
+    UnknownDevice(String),
+}
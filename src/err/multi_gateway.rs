@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MultiGatewayError {
+    #[error("device url {0} has no gateway id")]
+    InvalidDeviceUrl(String),
+    #[error("no client for gateway {0}")]
+    UnknownGateway(String),
+}
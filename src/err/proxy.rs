@@ -0,0 +1,37 @@
+use crate::err::http::RequestError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    #[error("missing or invalid api key")]
+    Unauthorized,
+
+    #[error("no device with label {0:?}")]
+    DeviceNotFound(String),
+
+    #[error("several devices are labeled {0:?}, use the device url instead")]
+    AmbiguousLabel(String),
+
+    #[error("invalid request body: {0}")]
+    InvalidBody(#[source] serde_json::Error),
+
+    #[error("gateway request failed: {0}")]
+    Gateway(#[from] RequestError),
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ProxyError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ProxyError::DeviceNotFound(_) => StatusCode::NOT_FOUND,
+            ProxyError::AmbiguousLabel(_) => StatusCode::CONFLICT,
+            ProxyError::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            ProxyError::Gateway(_) => StatusCode::BAD_GATEWAY,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("snapshot file {path:?} could not be accessed: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid snapshot: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+}
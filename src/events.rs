@@ -0,0 +1,312 @@
+use crate::api_client::ApiClient;
+use crate::commands::types::Event;
+use crate::err::http::RequestError;
+use http::StatusCode;
+use log::{debug, warn};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot, watch};
+use tokio::task::JoinHandle;
+
+pub(crate) const DEFAULT_CAPACITY: usize = 256;
+
+// The poll task unregisters its listener once `stop` fires or is dropped. It only checks
+// between polls, so a renewal in progress is finished and the renewed listener unregistered.
+#[derive(Debug)]
+struct Poller {
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<Result<(), RequestError>>>,
+}
+
+impl Drop for Poller {
+    // The last clone of the hub is gone, the task unregisters the listener in the background
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+/// Selects events by name and device URL. Empty lists match everything.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub device_urls: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        let name_matches = self.names.is_empty() || self.names.contains(&event.name);
+        let device_matches = self.device_urls.is_empty()
+            || event
+                .device_url
+                .as_ref()
+                .is_some_and(|url| self.device_urls.iter().any(|u| url == u));
+        name_matches && device_matches
+    }
+}
+
+/// Shares a single gateway event listener between any number of local subscribers.
+#[derive(Debug, Clone)]
+pub struct EventHub {
+    sender: broadcast::Sender<Arc<Event>>,
+    renewals: Arc<watch::Sender<u64>>,
+    poller: Arc<Mutex<Option<Poller>>>,
+}
+
+impl EventHub {
+    /// A hub without a gateway listener, events are only delivered via `publish`.
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        let (renewals, _) = watch::channel(0);
+        Self {
+            sender,
+            renewals: Arc::new(renewals),
+            poller: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Registers an event listener and polls it every `poll_interval` until `stop` is called or
+    /// the last clone of the hub is dropped.
+    pub async fn start(api: ApiClient, poll_interval: Duration) -> Result<Self, RequestError> {
+        let hub = Self::new(DEFAULT_CAPACITY);
+        let mut listener_id = api.register_event_listener().await?.id;
+        let (stop, mut stopped) = oneshot::channel();
+
+        // Holds senders rather than the hub, so dropping the hub ends the task
+        let sender = hub.sender.clone();
+        let renewals = hub.renewals.clone();
+        let task = tokio::spawn(async move {
+            let mut poll = tokio::time::interval(poll_interval);
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = poll.tick() => {}
+                }
+                match api.fetch_events(&listener_id).await {
+                    Ok(events) => events.into_iter().for_each(|e| {
+                        let _ = sender.send(Arc::new(e));
+                    }),
+                    Err(e) if listener_expired(&e) => {
+                        warn!("Event listener {listener_id} is gone, registering a new one: {e}");
+                        match renew_listener(&api, &listener_id).await {
+                            Ok(renewed) => {
+                                listener_id = renewed;
+                                renewals.send_modify(|count| *count += 1);
+                            }
+                            Err(e) => warn!("Registering event listener failed: {e}"),
+                        }
+                    }
+                    Err(e) => warn!("Fetching events failed: {e}"),
+                }
+            }
+            api.unregister_event_listener(&listener_id).await.map(drop)
+        });
+
+        if let Ok(mut poller) = hub.poller.lock() {
+            *poller = Some(Poller {
+                stop: Some(stop),
+                task: Some(task),
+            });
+        }
+        Ok(hub)
+    }
+
+    /// Stops polling and unregisters the gateway listener.
+    pub async fn stop(&self) -> Result<(), RequestError> {
+        let poller = self.poller.lock().ok().and_then(|mut p| p.take());
+        let Some(mut poller) = poller else {
+            return Ok(());
+        };
+        if let Some(stop) = poller.stop.take() {
+            let _ = stop.send(());
+        }
+        match poller.task.take() {
+            Some(task) => task.await.map_err(|e| RequestError::Server(e.into()))?,
+            None => Ok(()),
+        }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // Sending only fails if nobody is subscribed
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+            filter,
+        }
+    }
+
+    /// Counts how often the gateway listener expired and was replaced. Events sent in between
+    /// are lost, so consumers keeping state should reload it when this changes.
+    pub fn renewals(&self) -> watch::Receiver<u64> {
+        self.renewals.subscribe()
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Whether fetching events failed because the gateway no longer knows the listener, e.g. after
+/// it expired from not being polled or the gateway rebooted.
+pub fn listener_expired(error: &RequestError) -> bool {
+    matches!(error, RequestError::Status { status, .. } if *status == StatusCode::BAD_REQUEST)
+}
+
+/// Unregisters `listener_id`, best effort, and registers a new listener in its place.
+pub async fn renew_listener(api: &ApiClient, listener_id: &str) -> Result<String, RequestError> {
+    if let Err(e) = api.unregister_event_listener(listener_id).await {
+        debug!("Unregistering event listener {listener_id} failed: {e}");
+    }
+    Ok(api.register_event_listener().await?.id)
+}
+
+#[derive(Debug)]
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<Arc<Event>>,
+    filter: EventFilter,
+}
+
+impl EventSubscription {
+    /// Next event matching the filter, or `None` once the hub is dropped. Subscribers that fall
+    /// behind skip the events they missed.
+    pub async fn recv(&mut self) -> Option<Arc<Event>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Some(event),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    debug!("Subscriber lagged behind, skipped {missed} events")
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, device_url: Option<&str>) -> Event {
+        serde_json::from_value(serde_json::json!({ "name": name, "deviceURL": device_url }))
+            .expect("should build event")
+    }
+
+    #[test]
+    fn filters_by_name_and_device() {
+        let filter = EventFilter {
+            names: vec!["DeviceStateChangedEvent".to_string()],
+            device_urls: vec!["io://1/1".to_string()],
+        };
+        assert!(filter.matches(&event("DeviceStateChangedEvent", Some("io://1/1"))));
+        assert!(!filter.matches(&event("DeviceStateChangedEvent", Some("io://1/2"))));
+        assert!(!filter.matches(&event("ExecutionStateChangedEvent", None)));
+        assert!(EventFilter::default().matches(&event("ExecutionStateChangedEvent", None)));
+    }
+
+    #[tokio::test]
+    async fn fans_out_to_filtered_subscribers() {
+        let hub = EventHub::new(16);
+        let mut all = hub.subscribe(EventFilter::default());
+        let mut device = hub.subscribe(EventFilter {
+            device_urls: vec!["io://1/2".to_string()],
+            ..EventFilter::default()
+        });
+        assert_eq!(hub.subscriber_count(), 2);
+
+        hub.publish(event("ExecutionStateChangedEvent", None));
+        hub.publish(event("DeviceStateChangedEvent", Some("io://1/2")));
+
+        let received = all.recv().await.expect("should receive event");
+        assert_eq!(received.name, "ExecutionStateChangedEvent");
+        let received = all.recv().await.expect("should receive event");
+        assert_eq!(received.name, "DeviceStateChangedEvent");
+        let received = device.recv().await.expect("should receive event");
+        assert_eq!(received.device_url.as_deref(), Some("io://1/2"));
+
+        drop(hub);
+        assert!(all.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn renews_only_expired_listeners() {
+        use crate::commands::traits::HttpMethod::POST;
+        use crate::transport::{MockTransport, RawResponse};
+
+        let mock = MockTransport::new()
+            .respond(POST, "/events/register", RawResponse::ok(r#"{"id": "a"}"#))
+            .respond(
+                POST,
+                "/events/a/fetch",
+                RawResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""),
+            );
+        let registered = |mock: &MockTransport| {
+            mock.requests()
+                .iter()
+                .filter(|r| r.path.ends_with("/events/register"))
+                .count()
+        };
+        let hub = EventHub::start(
+            ApiClient::with_transport(mock.clone()),
+            Duration::from_millis(5),
+        )
+        .await
+        .expect("should start");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(registered(&mock), 1, "transient errors keep the listener");
+
+        mock.clone()
+            .respond(
+                POST,
+                "/events/a/fetch",
+                RawResponse::new(StatusCode::BAD_REQUEST, ""),
+            )
+            .respond(POST, "/events/register", RawResponse::ok(r#"{"id": "b"}"#))
+            .respond(POST, "/events/b/fetch", RawResponse::ok("[]"))
+            .respond(POST, "/events/a/unregister", RawResponse::ok("[]"))
+            .respond(POST, "/events/b/unregister", RawResponse::ok("[]"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        hub.stop().await.expect("should stop");
+
+        let requests = mock.requests();
+        assert_eq!(registered(&mock), 2);
+        assert!(requests
+            .iter()
+            .any(|r| r.path.ends_with("/events/a/unregister")));
+        assert!(requests
+            .last()
+            .is_some_and(|r| r.path.ends_with("/events/b/unregister")));
+    }
+
+    #[tokio::test]
+    async fn unregisters_when_dropped() {
+        use crate::commands::traits::HttpMethod::POST;
+        use crate::transport::{MockTransport, RawResponse};
+
+        let mock = MockTransport::new()
+            .respond(POST, "/events/register", RawResponse::ok(r#"{"id": "a"}"#))
+            .respond(POST, "/events/a/fetch", RawResponse::ok("[]"))
+            .respond(POST, "/events/a/unregister", RawResponse::ok("[]"));
+        let hub = EventHub::start(
+            ApiClient::with_transport(mock.clone()),
+            Duration::from_millis(5),
+        )
+        .await
+        .expect("should start");
+        let mut subscription = hub.subscribe(EventFilter::default());
+
+        drop(hub);
+        assert!(subscription.recv().await.is_none());
+        assert!(mock
+            .requests()
+            .last()
+            .is_some_and(|r| r.path.ends_with("/events/a/unregister")));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::commands::types::Device;
+use crate::mqtt::{BridgeState, Message};
+use serde_json::{json, Value};
+
+const CLOSURE_STATE: &str = "core:ClosureState";
+const ON_OFF_STATE: &str = "core:OnOffState";
+const LIGHT_INTENSITY_STATE: &str = "core:LightIntensityState";
+
+// State name, device class and unit of measurement
+const SENSORS: &[(&str, &str, &str)] = &[
+    ("core:TemperatureState", "temperature", "°C"),
+    ("core:RelativeHumidityState", "humidity", "%"),
+    ("core:LuminanceState", "illuminance", "lx"),
+    ("core:ElectricEnergyConsumptionState", "energy", "Wh"),
+    ("core:ElectricPowerConsumptionState", "power", "W"),
+    ("core:BatteryLevelState", "battery", "%"),
+];
+
+// State name, device class and the values reported for on and off
+const BINARY_SENSORS: &[(&str, &str, &str, &str)] = &[
+    ("core:ContactState", "opening", "open", "closed"),
+    (
+        "core:OccupancyState",
+        "occupancy",
+        "personInside",
+        "noPersonInside",
+    ),
+    ("core:SmokeState", "smoke", "detected", "notDetected"),
+];
+
+/// Retained Home Assistant MQTT discovery configs for all devices of `bridge`, using the
+/// bridge's state, availability and command topics.
+pub fn discovery(bridge: &BridgeState, discovery_prefix: &str) -> Vec<Message> {
+    bridge
+        .devices()
+        .flat_map(|(segment, device)| entities(bridge, segment, device))
+        .map(|(component, object_id, config)| Message {
+            topic: format!("{discovery_prefix}/{component}/{object_id}/config"),
+            payload: config.to_string(),
+            retain: true,
+        })
+        .collect()
+}
+
+fn entities(
+    bridge: &BridgeState,
+    segment: &str,
+    device: &Device,
+) -> Vec<(&'static str, String, Value)> {
+    let id = object_id(&device.device_url);
+    let base = |name: Option<&str>, unique_id: &str| {
+        json!({
+            "name": name,
+            "unique_id": unique_id,
+            // Unavailable when either the bridge or the device is offline
+            "availability": [
+                { "topic": bridge.bridge_availability_topic() },
+                { "topic": bridge.availability_topic(segment) },
+            ],
+            "availability_mode": "all",
+            "device": {
+                "identifiers": [device.device_url],
+                "name": device.label,
+                "manufacturer": "Somfy",
+                "model": device
+                    .definition
+                    .as_ref()
+                    .map_or(device.controllable_name.as_str(), |d| d.widget_name.as_str()),
+            },
+        })
+    };
+    let mut entities = vec![];
+
+    let ui_class = device.definition.as_ref().map(|d| d.ui_class.as_str());
+    if let Some(device_class) = ui_class.and_then(cover_class) {
+        let mut config = base(None, &id);
+        extend(&mut config, cover(bridge, segment, device, device_class));
+        entities.push(("cover", id.clone(), config));
+    } else if ui_class == Some("Light") {
+        let mut config = base(None, &id);
+        extend(&mut config, light(bridge, segment, device));
+        entities.push(("light", id.clone(), config));
+    }
+
+    for (state, device_class, unit) in SENSORS {
+        if has_state(device, state) {
+            let unique_id = format!("{id}_{device_class}");
+            let mut config = base(Some(device_class), &unique_id);
+            extend(
+                &mut config,
+                json!({
+                    "state_topic": bridge.state_topic(segment, state),
+                    "device_class": device_class,
+                    "unit_of_measurement": unit,
+                    "state_class": "measurement",
+                }),
+            );
+            entities.push(("sensor", unique_id, config));
+        }
+    }
+
+    for (state, device_class, on, off) in BINARY_SENSORS {
+        if has_state(device, state) {
+            let unique_id = format!("{id}_{device_class}");
+            let mut config = base(Some(device_class), &unique_id);
+            extend(
+                &mut config,
+                json!({
+                    "state_topic": bridge.state_topic(segment, state),
+                    "device_class": device_class,
+                    "payload_on": on,
+                    "payload_off": off,
+                }),
+            );
+            entities.push(("binary_sensor", unique_id, config));
+        }
+    }
+
+    entities
+}
+
+fn cover(bridge: &BridgeState, segment: &str, device: &Device, device_class: &str) -> Value {
+    // Unsupported commands are disabled with null payloads
+    let payload = |command: &'static str| device.supports_command(command).then_some(command);
+    let mut config = json!({
+        "device_class": device_class,
+        "command_topic": bridge.command_topic(segment),
+        "payload_open": payload("open"),
+        "payload_close": payload("close"),
+        "payload_stop": payload("stop"),
+    });
+
+    if has_state(device, CLOSURE_STATE) {
+        // ClosureState is 0 when open and 100 when closed, Home Assistant scales positions accordingly
+        extend(
+            &mut config,
+            json!({
+                "position_topic": bridge.state_topic(segment, CLOSURE_STATE),
+                "position_open": 0,
+                "position_closed": 100,
+            }),
+        );
+        if device.supports_command("setClosure") {
+            extend(
+                &mut config,
+                json!({
+                    "set_position_topic": bridge.command_topic(segment),
+                    "set_position_template": "setClosure {{ position }}",
+                }),
+            );
+        }
+    }
+    config
+}
+
+fn light(bridge: &BridgeState, segment: &str, device: &Device) -> Value {
+    let mut config = json!({
+        "command_topic": bridge.command_topic(segment),
+        "payload_on": "on",
+        "payload_off": "off",
+    });
+    if has_state(device, ON_OFF_STATE) {
+        extend(
+            &mut config,
+            json!({
+                "state_topic": bridge.state_topic(segment, ON_OFF_STATE),
+                "state_value_template": "{{ value }}",
+            }),
+        );
+    }
+    if has_state(device, LIGHT_INTENSITY_STATE) && device.supports_command("setIntensity") {
+        extend(
+            &mut config,
+            json!({
+                "brightness_state_topic": bridge.state_topic(segment, LIGHT_INTENSITY_STATE),
+                "brightness_command_topic": bridge.command_topic(segment),
+                "brightness_command_template": "setIntensity {{ value }}",
+                "brightness_scale": 100,
+            }),
+        );
+    }
+    config
+}
+
+fn cover_class(ui_class: &str) -> Option<&'static str> {
+    Some(match ui_class {
+        "RollerShutter" | "Shutter" | "SwingingShutter" => "shutter",
+        "ExteriorScreen" | "Screen" => "shade",
+        "VenetianBlind" | "ExteriorVenetianBlind" => "blind",
+        "Awning" | "Pergola" => "awning",
+        "Curtain" => "curtain",
+        "GarageDoor" => "garage",
+        "Gate" => "gate",
+        "Window" => "window",
+        _ => return None,
+    })
+}
+
+// Current states, or states the definition says the device reports
+fn has_state(device: &Device, name: &str) -> bool {
+    device.states.iter().any(|s| s.name == name)
+        || device
+            .definition
+            .as_ref()
+            .is_some_and(|d| d.states.iter().any(|s| s.name == name))
+}
+
+fn object_id(device_url: &str) -> String {
+    let id: String = device_url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("somfy_{}", id.trim_matches('_'))
+}
+
+fn extend(config: &mut Value, extra: Value) {
+    if let (Value::Object(config), Value::Object(extra)) = (config, extra) {
+        config.extend(extra);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::types::Setup;
+
+    fn bridge() -> BridgeState {
+        let setup: Setup = serde_json::from_str(
+            r#"{
+            "gateways": [],
+            "devices": [
+                {"deviceURL": "io://0812-2424-9999/1", "label": "Office", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+                 "states": [{"name": "core:ClosureState", "type": 1, "value": 0}], "attributes": [],
+                 "definition": {"uiClass": "RollerShutter", "type": "ACTUATOR", "widgetName": "PositionableRollerShutter",
+                    "attributes": [], "states": [{"name": "core:ClosureState"}],
+                    "commands": [{"commandName": "open", "nparams": 0}, {"commandName": "close", "nparams": 0},
+                                 {"commandName": "setClosure", "nparams": 1}]}},
+                {"deviceURL": "io://0812-2424-9999/2", "label": "Hall", "controllableName": "io:Sensor",
+                 "subsystemId": 0, "type": 2, "available": true, "synced": true, "enabled": true,
+                 "states": [{"name": "core:TemperatureState", "type": 2, "value": "21"},
+                            {"name": "core:ContactState", "type": 3, "value": "closed"}], "attributes": []}
+            ]
+        }"#,
+        )
+        .expect("should parse setup");
+        BridgeState::new("somfy", setup)
+    }
+
+    fn config(messages: &[Message], topic: &str) -> Value {
+        let message = messages
+            .iter()
+            .find(|m| m.topic == topic)
+            .unwrap_or_else(|| panic!("should publish {topic}"));
+        assert!(message.retain);
+        serde_json::from_str(&message.payload).expect("should be json")
+    }
+
+    #[test]
+    fn maps_shutters_to_covers() {
+        let messages = discovery(&bridge(), "homeassistant");
+        let cover = config(
+            &messages,
+            "homeassistant/cover/somfy_io___0812_2424_9999_1/config",
+        );
+
+        assert_eq!(cover["device_class"], "shutter");
+        assert_eq!(cover["command_topic"], "somfy/Office/set");
+        assert_eq!(
+            cover["availability"],
+            json!([
+                { "topic": "somfy/bridge/availability" },
+                { "topic": "somfy/Office/availability" },
+            ])
+        );
+        assert_eq!(cover["availability_mode"], "all");
+        assert_eq!(cover["payload_open"], "open");
+        assert_eq!(cover["payload_stop"], Value::Null);
+        assert_eq!(cover["position_topic"], "somfy/Office/core:ClosureState");
+        assert_eq!(cover["position_closed"], 100);
+        assert_eq!(cover["set_position_template"], "setClosure {{ position }}");
+        assert_eq!(cover["device"]["model"], "PositionableRollerShutter");
+    }
+
+    #[test]
+    fn maps_sensor_states() {
+        let messages = discovery(&bridge(), "homeassistant");
+        assert_eq!(messages.len(), 3);
+
+        let sensor = config(
+            &messages,
+            "homeassistant/sensor/somfy_io___0812_2424_9999_2_temperature/config",
+        );
+        assert_eq!(sensor["state_topic"], "somfy/Hall/core:TemperatureState");
+        assert_eq!(sensor["unit_of_measurement"], "°C");
+
+        let contact = config(
+            &messages,
+            "homeassistant/binary_sensor/somfy_io___0812_2424_9999_2_opening/config",
+        );
+        assert_eq!(contact["payload_on"], "open");
+        assert_eq!(contact["device"]["identifiers"][0], "io://0812-2424-9999/2");
+    }
+}
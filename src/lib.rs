@@ -1,9 +1,44 @@
 pub mod api_client;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cassette;
+pub mod certs;
+pub mod device_url;
+#[cfg(feature = "client")]
+pub mod events;
+#[cfg(feature = "mqtt")]
+pub mod homeassistant;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod multi_gateway;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+pub mod recorder;
+#[cfg(feature = "test-util")]
+pub mod simulator;
+pub mod snapshot;
+#[cfg(feature = "client")]
+pub mod state_cache;
+pub mod transport;
+pub(crate) mod util;
 pub mod err {
+    pub mod cassette;
     pub mod cert;
+    pub mod config;
+    pub mod device_url;
     pub mod http;
+    #[cfg(feature = "mqtt")]
+    pub mod mqtt;
+    pub mod multi_gateway;
+    #[cfg(feature = "proxy")]
+    pub mod proxy;
+    pub mod recorder;
+    pub mod snapshot;
 }
-pub(crate) mod config {
+pub mod config {
+    pub mod builder;
+    pub mod loader;
+    pub mod pinning;
     pub(crate) mod tls_cert;
 }
 pub mod commands {
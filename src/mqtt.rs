@@ -0,0 +1,516 @@
+use crate::api_client::ApiClient;
+use crate::commands::types::{
+    Action, ActionGroup, Command, Device, Event, Setup, DEVICE_STATE_CHANGED_EVENT,
+};
+use crate::err::mqtt::MqttBridgeError;
+use crate::events::{listener_expired, renew_listener};
+use crate::homeassistant;
+use log::{debug, warn};
+use rumqttc::{AsyncClient, Event as MqttEvent, LastWill, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+
+// Reserved for the bridge's own availability
+const BRIDGE_SEGMENT: &str = "bridge";
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+const DEVICE_AVAILABLE_EVENT: &str = "DeviceAvailableEvent";
+const DEVICE_UNAVAILABLE_EVENT: &str = "DeviceUnavailableEvent";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeConfig {
+    /// Root of all topics, e.g. `somfy` for `somfy/<label>/<state>`
+    pub prefix: String,
+    pub poll_interval: Duration,
+    /// Publishes Home Assistant discovery configs under this prefix, usually `homeassistant`
+    pub discovery_prefix: Option<String>,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "somfy".to_string(),
+            poll_interval: Duration::from_secs(2),
+            discovery_prefix: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+}
+
+// Payload of a set topic, either `{"name": "setClosure", "parameters": ["50"]}` or `setClosure 50`
+#[derive(Debug, Deserialize)]
+struct CommandPayload {
+    name: String,
+    #[serde(default)]
+    parameters: Vec<serde_json::Value>,
+}
+
+/// Maps devices to topics and translates between gateway events and MQTT messages,
+/// without any I/O.
+#[derive(Debug, Clone)]
+pub struct BridgeState {
+    prefix: String,
+    // Topic segment and device, segments are unique
+    devices: Vec<(String, Device)>,
+}
+
+impl BridgeState {
+    pub fn new(prefix: impl Into<String>, setup: Setup) -> Self {
+        let mut devices: Vec<(String, Device)> = vec![];
+        for device in setup.devices {
+            let base = topic_segment(&device.label);
+            let mut segment = base.clone();
+            let mut n = 2;
+            while segment == BRIDGE_SEGMENT || devices.iter().any(|(s, _)| *s == segment) {
+                segment = format!("{base}_{n}");
+                n += 1;
+            }
+            if segment != base {
+                warn!(
+                    "Duplicate label {:?}, publishing as {segment}",
+                    device.label
+                );
+            }
+            devices.push((segment, device));
+        }
+
+        Self {
+            prefix: prefix.into(),
+            devices,
+        }
+    }
+
+    /// Devices with the topic segment they are published under.
+    pub fn devices(&self) -> impl Iterator<Item = (&str, &Device)> {
+        self.devices.iter().map(|(s, d)| (s.as_str(), d))
+    }
+
+    pub fn bridge_availability_topic(&self) -> String {
+        format!("{}/{BRIDGE_SEGMENT}/availability", self.prefix)
+    }
+
+    /// Topic filter matching the set topics of all devices.
+    pub fn command_filter(&self) -> String {
+        format!("{}/+/set", self.prefix)
+    }
+
+    /// Retained availability and state messages for all devices.
+    pub fn snapshot(&self) -> Vec<Message> {
+        let mut messages = vec![self.message(self.bridge_availability_topic(), ONLINE)];
+        for (segment, device) in &self.devices {
+            messages.push(self.availability(segment, device.available));
+            messages.extend(device.states.iter().map(|state| {
+                self.message(
+                    self.state_topic(segment, &state.name),
+                    state.value.to_string(),
+                )
+            }));
+        }
+        messages
+    }
+
+    /// Updates the known device states and returns the messages to publish for `event`.
+    pub fn apply_event(&mut self, event: &Event) -> Vec<Message> {
+        let Some(index) = self
+            .devices
+            .iter()
+            .position(|(_, d)| Some(&d.device_url) == event.device_url.as_ref())
+        else {
+            return vec![];
+        };
+
+        let (segment, device) = &mut self.devices[index];
+        let segment = segment.clone();
+        match event.name.as_str() {
+            DEVICE_STATE_CHANGED_EVENT => {
+                for changed in &event.device_states {
+                    match device.states.iter_mut().find(|s| s.name == changed.name) {
+                        Some(state) => state.value = changed.value.clone(),
+                        None => device.states.push(changed.clone()),
+                    }
+                }
+                event
+                    .device_states
+                    .iter()
+                    .map(|state| {
+                        self.message(
+                            self.state_topic(&segment, &state.name),
+                            state.value.to_string(),
+                        )
+                    })
+                    .collect()
+            }
+            DEVICE_AVAILABLE_EVENT | DEVICE_UNAVAILABLE_EVENT => {
+                device.available = event.name == DEVICE_AVAILABLE_EVENT;
+                vec![self.availability(&segment, event.name == DEVICE_AVAILABLE_EVENT)]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Turns a message on a set topic into an action group for the addressed device.
+    pub fn action_group(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> Result<ActionGroup, MqttBridgeError> {
+        let (_, device) = self
+            .devices
+            .iter()
+            .find(|(s, _)| self.command_topic(s) == topic)
+            .ok_or_else(|| MqttBridgeError::UnknownDevice(topic.to_string()))?;
+
+        let invalid = || MqttBridgeError::InvalidCommand {
+            topic: topic.to_string(),
+            payload: String::from_utf8_lossy(payload).to_string(),
+        };
+        let payload = std::str::from_utf8(payload).map_err(|_| invalid())?.trim();
+        let command = if payload.starts_with('{') {
+            let parsed: CommandPayload = serde_json::from_str(payload).map_err(|_| invalid())?;
+            Command {
+                name: parsed.name,
+                parameters: parsed
+                    .parameters
+                    .into_iter()
+                    .map(|p| match p {
+                        serde_json::Value::String(s) => s,
+                        other => other.to_string(),
+                    })
+                    .collect(),
+            }
+        } else {
+            let mut parts = payload.split_whitespace();
+            Command {
+                name: parts.next().ok_or_else(invalid)?.to_string(),
+                parameters: parts.map(str::to_string).collect(),
+            }
+        };
+
+        Ok(ActionGroup {
+            label: Some(format!("mqtt: {}", command.name)),
+            actions: vec![Action {
+                device_url: device.device_url.clone(),
+                commands: vec![command],
+            }],
+        })
+    }
+
+    pub fn state_topic(&self, segment: &str, state_name: &str) -> String {
+        // Keeps the namespace, core:OpenClosedState and io:OpenClosedState are different states
+        format!("{}/{segment}/{}", self.prefix, topic_segment(state_name))
+    }
+
+    pub fn availability_topic(&self, segment: &str) -> String {
+        format!("{}/{segment}/availability", self.prefix)
+    }
+
+    pub fn command_topic(&self, segment: &str) -> String {
+        format!("{}/{segment}/set", self.prefix)
+    }
+
+    fn availability(&self, segment: &str, available: bool) -> Message {
+        self.message(
+            self.availability_topic(segment),
+            if available { ONLINE } else { OFFLINE },
+        )
+    }
+
+    fn message(&self, topic: String, payload: impl Into<String>) -> Message {
+        Message {
+            topic,
+            payload: payload.into(),
+            retain: true,
+        }
+    }
+}
+
+// Keeps topics free of wildcards and separators
+fn topic_segment(name: &str) -> String {
+    let segment: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' | '\0' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    if segment.is_empty() {
+        "_".to_string()
+    } else {
+        segment
+    }
+}
+
+struct CommandInput {
+    topic: String,
+    payload: Vec<u8>,
+}
+
+/// Publishes device states to MQTT and executes commands received on set topics until
+/// interrupted with Ctrl-C.
+pub async fn run(
+    api: &ApiClient,
+    mut mqtt_options: MqttOptions,
+    config: BridgeConfig,
+) -> Result<(), MqttBridgeError> {
+    let mut state = BridgeState::new(config.prefix, api.get_setup().await?);
+    let discovery = config
+        .discovery_prefix
+        .map(|prefix| homeassistant::discovery(&state, &prefix))
+        .unwrap_or_default();
+    mqtt_options.set_last_will(LastWill::new(
+        state.bridge_availability_topic(),
+        OFFLINE,
+        QoS::AtLeastOnce,
+        true,
+    ));
+    let (client, mut event_loop) = AsyncClient::new(mqtt_options, 64);
+
+    // The event loop must keep being polled while the loop below publishes, so it never waits
+    // on the loop below: commands are dropped when it falls behind
+    let connected = Arc::new(Notify::new());
+    let (tx, mut rx) = mpsc::channel(64);
+    let connection = tokio::spawn({
+        let connected = connected.clone();
+        async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(MqttEvent::Incoming(Packet::ConnAck(_))) => connected.notify_one(),
+                    // Retained commands were sent before this connection, running them again
+                    // would move devices unprompted
+                    Ok(MqttEvent::Incoming(Packet::Publish(publish))) if publish.retain => {
+                        warn!("Ignoring retained command on {}", publish.topic)
+                    }
+                    Ok(MqttEvent::Incoming(Packet::Publish(publish))) => {
+                        let command = CommandInput {
+                            topic: publish.topic,
+                            payload: publish.payload.to_vec(),
+                        };
+                        match tx.try_send(command) {
+                            Ok(()) => {}
+                            Err(mpsc::error::TrySendError::Full(command)) => {
+                                warn!("Bridge is busy, dropping command on {}", command.topic)
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => return,
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        // The event loop reconnects on the next poll
+                        warn!("MQTT connection error: {e}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut listener_id = api.register_event_listener().await?.id;
+    let mut poll = tokio::time::interval(config.poll_interval);
+    // Errors end the loop but not the function, so the cleanup below always runs
+    let result = async {
+        loop {
+            tokio::select! {
+                _ = poll.tick() => match api.fetch_events(&listener_id).await {
+                    Ok(events) => {
+                        for event in &events {
+                            publish(&client, state.apply_event(event)).await?;
+                        }
+                    }
+                    Err(e) if listener_expired(&e) => {
+                        warn!("Event listener {listener_id} is gone, registering a new one: {e}");
+                        match renew_listener(api, &listener_id).await {
+                            Ok(renewed) => listener_id = renewed,
+                            Err(e) => warn!("Registering event listener failed: {e}"),
+                        }
+                    }
+                    Err(e) => warn!("Fetching events failed: {e}"),
+                },
+                // Subscriptions and retained states are restored on every (re)connect
+                _ = connected.notified() => {
+                    client.subscribe(state.command_filter(), QoS::AtLeastOnce).await?;
+                    publish(&client, discovery.clone()).await?;
+                    publish(&client, state.snapshot()).await?;
+                }
+                input = rx.recv() => match input {
+                    Some(CommandInput { topic, payload }) => {
+                        match state.action_group(&topic, &payload) {
+                            Ok(action_group) => match api.execute_actions(&action_group).await {
+                                Ok(execution) => debug!("{topic} started {}", execution.exec_id),
+                                Err(e) => warn!("Executing command from {topic} failed: {e}"),
+                            },
+                            Err(e) => warn!("{e}"),
+                        }
+                    }
+                    None => return Ok(()),
+                },
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+        }
+    }
+    .await;
+
+    let offline = client
+        .publish(
+            state.bridge_availability_topic(),
+            QoS::AtLeastOnce,
+            true,
+            OFFLINE,
+        )
+        .await
+        .map_err(MqttBridgeError::from);
+    let disconnected = client.disconnect().await.map_err(MqttBridgeError::from);
+    // Give the event loop the chance to flush the offline message and disconnect
+    let _ = tokio::time::timeout(Duration::from_secs(1), connection).await;
+    let unregistered = api
+        .unregister_event_listener(&listener_id)
+        .await
+        .map(drop)
+        .map_err(MqttBridgeError::from);
+    result.and(offline).and(disconnected).and(unregistered)
+}
+
+async fn publish(client: &AsyncClient, messages: Vec<Message>) -> Result<(), MqttBridgeError> {
+    for message in messages {
+        client
+            .publish(
+                message.topic,
+                QoS::AtLeastOnce,
+                message.retain,
+                message.payload,
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> BridgeState {
+        let setup: Setup = serde_json::from_str(
+            r#"{
+            "gateways": [],
+            "devices": [
+                {"deviceURL": "io://1/1", "label": "Living room", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+                 "states": [{"name": "core:ClosureState", "type": 1, "value": 0}], "attributes": []},
+                {"deviceURL": "io://1/2", "label": "Living room", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": false, "synced": true, "enabled": true,
+                 "states": [], "attributes": []}
+            ]
+        }"#,
+        )
+        .expect("should parse setup");
+        BridgeState::new("somfy", setup)
+    }
+
+    fn message(topic: &str, payload: &str) -> Message {
+        Message {
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+            retain: true,
+        }
+    }
+
+    #[test]
+    fn publishes_retained_snapshot() {
+        assert_eq!(
+            state().snapshot(),
+            vec![
+                message("somfy/bridge/availability", "online"),
+                message("somfy/Living_room/availability", "online"),
+                message("somfy/Living_room/core:ClosureState", "0"),
+                message("somfy/Living_room_2/availability", "offline"),
+            ]
+        );
+    }
+
+    #[test]
+    fn publishes_state_changes_and_availability() {
+        let mut state = state();
+        let event: Event = serde_json::from_str(
+            r#"{"name": "DeviceStateChangedEvent", "deviceURL": "io://1/1",
+                "deviceStates": [{"name": "core:ClosureState", "type": 1, "value": "50"}]}"#,
+        )
+        .expect("should parse event");
+        assert_eq!(
+            state.apply_event(&event),
+            vec![message("somfy/Living_room/core:ClosureState", "50")]
+        );
+
+        let event: Event =
+            serde_json::from_str(r#"{"name": "DeviceAvailableEvent", "deviceURL": "io://1/2"}"#)
+                .expect("should parse event");
+        assert_eq!(
+            state.apply_event(&event),
+            vec![message("somfy/Living_room_2/availability", "online")]
+        );
+
+        let event: Event = serde_json::from_str(r#"{"name": "ExecutionStateChangedEvent"}"#)
+            .expect("should parse event");
+        assert!(state.apply_event(&event).is_empty());
+    }
+
+    #[test]
+    fn parses_commands_from_set_topics() {
+        let state = state();
+        let action_group = state
+            .action_group("somfy/Living_room_2/set", b"setClosure 50")
+            .expect("should parse plain command");
+        assert_eq!(action_group.actions[0].device_url, "io://1/2");
+        assert_eq!(action_group.actions[0].commands[0].name, "setClosure");
+        assert_eq!(action_group.actions[0].commands[0].parameters, vec!["50"]);
+
+        let action_group = state
+            .action_group(
+                "somfy/Living_room/set",
+                br#"{"name": "setClosure", "parameters": [50]}"#,
+            )
+            .expect("should parse json command");
+        assert_eq!(action_group.actions[0].commands[0].parameters, vec!["50"]);
+    }
+
+    #[test]
+    fn rejects_unknown_devices_and_empty_commands() {
+        let state = state();
+        assert!(matches!(
+            state.action_group("somfy/Kitchen/set", b"open"),
+            Err(MqttBridgeError::UnknownDevice(_))
+        ));
+        assert!(matches!(
+            state.action_group("somfy/Living_room/set", b"  "),
+            Err(MqttBridgeError::InvalidCommand { .. })
+        ));
+    }
+
+    #[test]
+    fn sanitizes_topic_segments() {
+        assert_eq!(topic_segment("Garage/Door #1"), "Garage_Door__1");
+        assert_eq!(topic_segment(" "), "_");
+
+        let setup: Setup = serde_json::from_str(
+            r#"{"gateways": [], "devices": [
+                {"deviceURL": "io://1/1", "label": "bridge", "controllableName": "io:Shutter",
+                 "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+                 "states": [], "attributes": []}]}"#,
+        )
+        .expect("should parse setup");
+        let state = BridgeState::new("somfy", setup);
+        assert!(state.action_group("somfy/bridge_2/set", b"open").is_ok());
+        assert_ne!(
+            state.state_topic("bridge_2", "core:OpenClosedState"),
+            state.state_topic("bridge_2", "io:OpenClosedState")
+        );
+    }
+}
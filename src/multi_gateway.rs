@@ -0,0 +1,527 @@
+use crate::api_client::ApiClient;
+use crate::commands::cancel_execution::CancelExecutionResponse;
+use crate::commands::get_device::GetDeviceResponse;
+use crate::commands::get_device_state::GetDeviceStateResponse;
+use crate::commands::get_device_states::GetDeviceStatesResponse;
+use crate::commands::get_devices::GetDevicesResponse;
+use crate::commands::get_devices_by_controllable::GetDevicesByControllableResponse;
+use crate::commands::get_execution::GetExecutionResponse;
+use crate::commands::get_setup::GetSetupResponse;
+use crate::commands::get_setup_gateways::GetGatewaysResponse;
+use crate::commands::types::{Event, Setup};
+use crate::device_url::DeviceUrl;
+use crate::err::http::RequestError;
+use crate::err::multi_gateway::MultiGatewayError;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "generic-exec")]
+use {
+    crate::commands::execute_action_group::ExecuteActionGroupResponse,
+    crate::commands::types::ActionGroup, log::warn,
+};
+#[cfg(feature = "client")]
+use {
+    crate::events::{EventFilter, EventHub, EventSubscription, DEFAULT_CAPACITY},
+    std::time::Duration,
+    tokio::task::JoinHandle,
+};
+
+/// `ApiClient`s of several gateways, keyed by gateway PIN. Device level calls go to the gateway
+/// named in the device URL, e.g. `0000-1111-2222` for `io://0000-1111-2222/12345678`, setup
+/// level calls are sent to all gateways and their results merged. Merged calls fail with the
+/// first gateway that fails, use `gateway` to query the others on their own.
+#[derive(Debug, Clone, Default)]
+pub struct MultiGatewayClient {
+    clients: BTreeMap<String, ApiClient>,
+}
+
+impl MultiGatewayClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clients for `gateways`, given as gateway PIN and API key, see `ApiClient::from`.
+    #[cfg(feature = "client")]
+    pub async fn from(gateways: &[(&str, &str)]) -> Result<Self, RequestError> {
+        let mut client = Self::new();
+        for (id, api_key) in gateways {
+            client = client.with_gateway(id, ApiClient::from(id, api_key).await?);
+        }
+        Ok(client)
+    }
+
+    /// Adds `client` for the gateway with PIN `gateway_id`, replacing an earlier one.
+    pub fn with_gateway(mut self, gateway_id: &str, client: ApiClient) -> Self {
+        self.clients.insert(gateway_id.to_string(), client);
+        self
+    }
+
+    pub fn gateway(&self, gateway_id: &str) -> Option<&ApiClient> {
+        self.clients.get(gateway_id)
+    }
+
+    pub fn gateway_ids(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(String::as_str)
+    }
+
+    /// The client of the gateway a device belongs to, for calls not covered here.
+    pub fn gateway_for(&self, device_url: &DeviceUrl) -> Result<&ApiClient, RequestError> {
+        self.known_gateway(Self::gateway_id_of(device_url)?)
+    }
+
+    fn gateway_id_of(device_url: &DeviceUrl) -> Result<&str, RequestError> {
+        device_url.gateway_id().ok_or_else(|| {
+            RequestError::Server(MultiGatewayError::InvalidDeviceUrl(device_url.to_string()).into())
+        })
+    }
+
+    fn known_gateway(&self, gateway_id: &str) -> Result<&ApiClient, RequestError> {
+        self.gateway(gateway_id).ok_or_else(|| {
+            RequestError::Server(MultiGatewayError::UnknownGateway(gateway_id.to_string()).into())
+        })
+    }
+
+    pub async fn get_gateways(&self) -> Result<GetGatewaysResponse, RequestError> {
+        let mut gateways = vec![];
+        for client in self.clients.values() {
+            gateways.extend(client.get_gateways().await?);
+        }
+        Ok(gateways)
+    }
+
+    pub async fn get_devices(&self) -> Result<GetDevicesResponse, RequestError> {
+        let mut devices = vec![];
+        for client in self.clients.values() {
+            devices.extend(client.get_devices().await?);
+        }
+        Ok(devices)
+    }
+
+    /// The gateways and devices of all setups. Places are taken from the first gateway by PIN
+    /// that has any.
+    pub async fn get_setup(&self) -> Result<GetSetupResponse, RequestError> {
+        let mut merged = Setup {
+            gateways: vec![],
+            devices: vec![],
+            root_place: None,
+        };
+        for client in self.clients.values() {
+            let setup = client.get_setup().await?;
+            merged.gateways.extend(setup.gateways);
+            merged.devices.extend(setup.devices);
+            merged.root_place = merged.root_place.or(setup.root_place);
+        }
+        Ok(merged)
+    }
+
+    pub async fn get_device(
+        &self,
+        device_url: &DeviceUrl,
+    ) -> Result<GetDeviceResponse, RequestError> {
+        self.gateway_for(device_url)?.get_device(device_url).await
+    }
+
+    pub async fn get_device_states(
+        &self,
+        device_url: &DeviceUrl,
+    ) -> Result<GetDeviceStatesResponse, RequestError> {
+        self.gateway_for(device_url)?
+            .get_device_states(device_url)
+            .await
+    }
+
+    pub async fn get_device_state(
+        &self,
+        device_url: &DeviceUrl,
+        state_name: &str,
+    ) -> Result<GetDeviceStateResponse, RequestError> {
+        self.gateway_for(device_url)?
+            .get_device_state(device_url, state_name)
+            .await
+    }
+
+    pub async fn get_devices_by_controllable(
+        &self,
+        controllable_name: &str,
+    ) -> Result<GetDevicesByControllableResponse, RequestError> {
+        let mut device_urls = vec![];
+        for client in self.clients.values() {
+            device_urls.extend(
+                client
+                    .get_devices_by_controllable(controllable_name)
+                    .await?,
+            );
+        }
+        Ok(device_urls)
+    }
+
+    /// Runs the actions of `action_group` on the gateways of their devices, one execution per
+    /// gateway under the same label. Returns the execution ids by gateway PIN. If a gateway
+    /// rejects its part, the parts already started are cancelled, best effort.
+    #[cfg(feature = "generic-exec")]
+    pub async fn execute_actions(
+        &self,
+        action_group: &ActionGroup,
+    ) -> Result<BTreeMap<String, ExecuteActionGroupResponse>, RequestError> {
+        let mut groups: BTreeMap<&str, ActionGroup> = BTreeMap::new();
+        for action in &action_group.actions {
+            let gateway_id = Self::gateway_id_of(&action.device_url)?;
+            // Unknown gateways are rejected before anything runs
+            self.known_gateway(gateway_id)?;
+            groups
+                .entry(gateway_id)
+                .or_insert_with(|| ActionGroup {
+                    label: action_group.label.clone(),
+                    actions: vec![],
+                })
+                .actions
+                .push(action.clone());
+        }
+
+        let mut started = BTreeMap::new();
+        for (gateway_id, group) in groups {
+            match self
+                .known_gateway(gateway_id)?
+                .execute_actions(&group)
+                .await
+            {
+                Ok(execution) => {
+                    started.insert(gateway_id.to_string(), execution);
+                }
+                Err(e) => {
+                    for (gateway_id, execution) in &started {
+                        if let Err(e) = self.cancel_execution(gateway_id, &execution.exec_id).await
+                        {
+                            warn!("Cancelling {} failed: {e}", execution.exec_id);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(started)
+    }
+
+    pub async fn get_execution(
+        &self,
+        gateway_id: &str,
+        execution_id: &str,
+    ) -> Result<GetExecutionResponse, RequestError> {
+        self.known_gateway(gateway_id)?
+            .get_execution(execution_id)
+            .await
+    }
+
+    pub async fn cancel_execution(
+        &self,
+        gateway_id: &str,
+        execution_id: &str,
+    ) -> Result<CancelExecutionResponse, RequestError> {
+        self.known_gateway(gateway_id)?
+            .cancel_execution(execution_id)
+            .await
+    }
+
+    /// Registers an event listener on every gateway, returns the listener ids by gateway PIN.
+    pub async fn register_event_listeners(&self) -> Result<HashMap<String, String>, RequestError> {
+        let mut listeners = HashMap::new();
+        for (gateway_id, client) in &self.clients {
+            let listener = client.register_event_listener().await?;
+            listeners.insert(gateway_id.clone(), listener.id);
+        }
+        Ok(listeners)
+    }
+
+    /// New events of all `listeners`, with `Event::gateway_id` set to the gateway they came from.
+    pub async fn fetch_events(
+        &self,
+        listeners: &HashMap<String, String>,
+    ) -> Result<Vec<Event>, RequestError> {
+        let mut events = vec![];
+        for (gateway_id, listener_id) in listeners {
+            let fetched = self
+                .known_gateway(gateway_id)?
+                .fetch_events(listener_id)
+                .await?;
+            events.extend(fetched.into_iter().map(|e| tag(e, gateway_id)));
+        }
+        Ok(events)
+    }
+
+    pub async fn unregister_event_listeners(
+        &self,
+        listeners: &HashMap<String, String>,
+    ) -> Result<(), RequestError> {
+        for (gateway_id, listener_id) in listeners {
+            if let Some(client) = self.gateway(gateway_id) {
+                client.unregister_event_listener(listener_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Polls the events of all gateways every `poll_interval` and merges them into one hub,
+    /// until `MultiGatewayEvents::stop` is called or it is dropped.
+    #[cfg(feature = "client")]
+    pub async fn start_events(
+        &self,
+        poll_interval: Duration,
+    ) -> Result<MultiGatewayEvents, RequestError> {
+        let mut events = MultiGatewayEvents {
+            hub: EventHub::new(DEFAULT_CAPACITY),
+            gateways: vec![],
+            forwarders: vec![],
+        };
+        for (gateway_id, client) in &self.clients {
+            let gateway = match EventHub::start(client.clone(), poll_interval).await {
+                Ok(gateway) => gateway,
+                Err(e) => {
+                    let _ = events.stop().await;
+                    return Err(e);
+                }
+            };
+            let mut subscription = gateway.subscribe(EventFilter::default());
+            let hub = events.hub.clone();
+            let gateway_id = gateway_id.clone();
+            events.forwarders.push(tokio::spawn(async move {
+                while let Some(event) = subscription.recv().await {
+                    hub.publish(tag((*event).clone(), &gateway_id));
+                }
+            }));
+            events.gateways.push(gateway);
+        }
+        Ok(events)
+    }
+}
+
+/// The merged events of several gateways, see `MultiGatewayClient::start_events`.
+#[cfg(feature = "client")]
+#[derive(Debug)]
+pub struct MultiGatewayEvents {
+    hub: EventHub,
+    gateways: Vec<EventHub>,
+    forwarders: Vec<JoinHandle<()>>,
+}
+
+#[cfg(feature = "client")]
+impl MultiGatewayEvents {
+    pub fn hub(&self) -> &EventHub {
+        &self.hub
+    }
+
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        self.hub.subscribe(filter)
+    }
+
+    /// Stops polling and unregisters the listeners of all gateways.
+    pub async fn stop(self) -> Result<(), RequestError> {
+        self.forwarders.iter().for_each(JoinHandle::abort);
+        let mut result = Ok(());
+        for gateway in &self.gateways {
+            result = result.and(gateway.stop().await);
+        }
+        result
+    }
+}
+
+fn tag(event: Event, gateway_id: &str) -> Event {
+    Event {
+        gateway_id: Some(gateway_id.to_string()),
+        ..event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::traits::HttpMethod;
+    use crate::transport::{MockTransport, RawResponse};
+
+    fn device(device_url: &str) -> serde_json::Value {
+        serde_json::json!({
+            "deviceURL": device_url,
+            "label": device_url,
+            "controllableName": "io:RollerShutterGenericIOComponent",
+            "subsystemId": 0,
+            "type": 1,
+            "available": true,
+            "synced": true,
+            "enabled": true,
+            "states": [],
+            "attributes": []
+        })
+    }
+
+    fn gateway(gateway_id: &str) -> MockTransport {
+        let url = format!("io://{gateway_id}/1");
+        MockTransport::new()
+            .respond(
+                HttpMethod::GET,
+                "/setup/devices",
+                RawResponse::ok(serde_json::json!([device(&url)]).to_string()),
+            )
+            .respond(
+                HttpMethod::GET,
+                &format!("/setup/devices/{}", urlencoding::encode(&url)),
+                RawResponse::ok(device(&url).to_string()),
+            )
+            .respond(
+                HttpMethod::POST,
+                "/events/register",
+                RawResponse::ok(format!(r#"{{"id": "{gateway_id}-listener"}}"#)),
+            )
+            .respond(
+                HttpMethod::POST,
+                &format!("/events/{gateway_id}-listener/fetch"),
+                RawResponse::ok(r#"[{"name": "ExecutionStateChangedEvent"}]"#),
+            )
+            .respond(
+                HttpMethod::POST,
+                &format!("/events/{gateway_id}-listener/unregister"),
+                RawResponse::ok("[]"),
+            )
+            .respond(
+                HttpMethod::POST,
+                "/exec/apply",
+                RawResponse::ok(format!(r#"{{"execId": "{gateway_id}-exec"}}"#)),
+            )
+            .respond(
+                HttpMethod::DELETE,
+                &format!("/exec/current/setup/{gateway_id}-exec"),
+                RawResponse::ok("{}"),
+            )
+    }
+
+    fn client() -> (MultiGatewayClient, MockTransport, MockTransport) {
+        let (first, second) = (gateway("0000-1111-2222"), gateway("0000-1111-3333"));
+        let client = MultiGatewayClient::new()
+            .with_gateway("0000-1111-2222", ApiClient::with_transport(first.clone()))
+            .with_gateway("0000-1111-3333", ApiClient::with_transport(second.clone()));
+        (client, first, second)
+    }
+
+    #[tokio::test]
+    async fn routes_device_calls_and_merges_devices() {
+        let (client, _, second) = client();
+
+        let device = client
+            .get_device(&"io://0000-1111-3333/1".parse().expect("should parse"))
+            .await
+            .expect("should get device");
+        assert_eq!(device.device_url, "io://0000-1111-3333/1");
+        assert_eq!(second.requests().len(), 1);
+        assert!(client
+            .get_device(&"io://0000-1111-9999/1".parse().expect("should parse"))
+            .await
+            .is_err());
+        let unknown = DeviceUrl::from("unknown-shape".to_string());
+        assert!(matches!(
+            client.gateway_for(&unknown),
+            Err(RequestError::Server(e))
+                if matches!(e.downcast_ref(), Some(MultiGatewayError::InvalidDeviceUrl(_)))
+        ));
+
+        let devices = client.get_devices().await.expect("should get devices");
+        let urls: Vec<_> = devices.iter().map(|d| d.device_url.as_str()).collect();
+        assert_eq!(urls, ["io://0000-1111-2222/1", "io://0000-1111-3333/1"]);
+
+        let listeners = client
+            .register_event_listeners()
+            .await
+            .expect("should register");
+        let mut gateways: Vec<_> = client
+            .fetch_events(&listeners)
+            .await
+            .expect("should fetch events")
+            .into_iter()
+            .filter_map(|e| e.gateway_id)
+            .collect();
+        gateways.sort();
+        assert_eq!(gateways, ["0000-1111-2222", "0000-1111-3333"]);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn merges_event_streams() {
+        let (client, first, second) = client();
+        let events = client
+            .start_events(Duration::from_millis(10))
+            .await
+            .expect("should start events");
+        let mut subscription = events.subscribe(EventFilter::default());
+
+        let mut gateways = vec![];
+        while gateways.len() < 2 {
+            let event = subscription.recv().await.expect("should receive event");
+            let gateway_id = event.gateway_id.clone().expect("should be tagged");
+            if !gateways.contains(&gateway_id) {
+                gateways.push(gateway_id);
+            }
+        }
+        events.stop().await.expect("should stop");
+
+        // Dropping without stop still ends polling and unregisters every listener
+        let events = client
+            .start_events(Duration::from_millis(5))
+            .await
+            .expect("should start events");
+        let mut subscription = events.subscribe(EventFilter::default());
+        drop(events);
+        while subscription.recv().await.is_some() {}
+        for gateway in [first, second] {
+            let unregistered = gateway
+                .requests()
+                .iter()
+                .filter(|r| r.path.ends_with("/unregister"))
+                .count();
+            assert_eq!(unregistered, 2);
+        }
+    }
+
+    #[cfg(feature = "generic-exec")]
+    #[tokio::test]
+    async fn splits_executions_by_gateway() {
+        use crate::commands::types::Action;
+
+        let (client, first, second) = client();
+        let action = |device_url: &str| Action {
+            device_url: device_url.parse().expect("should parse"),
+            commands: vec![],
+        };
+        let action_group = ActionGroup {
+            label: Some("Evening".to_string()),
+            actions: vec![
+                action("io://0000-1111-2222/1"),
+                action("io://0000-1111-3333/1"),
+                action("io://0000-1111-2222/2"),
+            ],
+        };
+
+        let executions = client
+            .execute_actions(&action_group)
+            .await
+            .expect("should execute");
+        assert_eq!(executions["0000-1111-2222"].exec_id, "0000-1111-2222-exec");
+        assert_eq!(executions["0000-1111-3333"].exec_id, "0000-1111-3333-exec");
+        let sent: ActionGroup = serde_json::from_str(
+            second.requests()[0]
+                .body
+                .as_deref()
+                .expect("should have body"),
+        )
+        .expect("should be an action group");
+        assert_eq!(sent.actions, vec![action("io://0000-1111-3333/1")]);
+
+        // The part already started on the first gateway is cancelled
+        second.clone().respond(
+            HttpMethod::POST,
+            "/exec/apply",
+            RawResponse::new(http::StatusCode::SERVICE_UNAVAILABLE, ""),
+        );
+        assert!(client.execute_actions(&action_group).await.is_err());
+        assert!(first
+            .requests()
+            .iter()
+            .any(|r| r.method == HttpMethod::DELETE
+                && r.path.ends_with("/exec/current/setup/0000-1111-2222-exec")));
+    }
+}
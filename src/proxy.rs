@@ -0,0 +1,457 @@
+use crate::api_client::ApiClient;
+use crate::commands::types::{
+    Action, ActionGroup, ActionGroupExecution, Command, Device, DeviceStateValue,
+};
+use crate::device_url::DeviceUrl;
+use crate::err::proxy::ProxyError;
+use crate::events::{EventFilter, EventHub};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use axum::response::Response;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProxyConfig {
+    /// Keys accepted as `Authorization: Bearer <key>` or `X-API-Key: <key>`, no key is accepted if empty
+    pub api_keys: Vec<String>,
+    /// Origins allowed to call the proxy from a browser, `*` allows any origin
+    pub cors_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceView {
+    pub label: String,
+    pub device_url: DeviceUrl,
+    pub ui_class: Option<String>,
+    pub available: bool,
+    pub states: BTreeMap<String, DeviceStateValue>,
+}
+
+impl From<Device> for DeviceView {
+    fn from(device: Device) -> Self {
+        Self {
+            ui_class: device.definition.map(|d| d.ui_class),
+            states: device
+                .states
+                .into_iter()
+                .map(|s| (s.name, s.value))
+                .collect(),
+            label: device.label,
+            device_url: device.device_url,
+            available: device.available,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExecutionView {
+    pub id: String,
+    pub state: String,
+    pub label: Option<String>,
+    pub start_time: i64,
+    pub device_urls: Vec<DeviceUrl>,
+}
+
+impl From<ActionGroupExecution> for ExecutionView {
+    fn from(execution: ActionGroupExecution) -> Self {
+        Self {
+            id: execution.id,
+            state: execution.state,
+            label: execution.action_group.label,
+            start_time: execution.start_time,
+            device_urls: execution
+                .action_group
+                .actions
+                .into_iter()
+                .map(|a| a.device_url)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CommandBody {
+    #[serde(default)]
+    parameters: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExecutionStarted {
+    exec_id: String,
+}
+
+// Comma separated lists, e.g. `?names=DeviceStateChangedEvent&device_urls=io://1/1,io://1/2`
+#[derive(Debug, Default, Deserialize)]
+struct EventQuery {
+    names: Option<String>,
+    device_urls: Option<String>,
+}
+
+impl From<EventQuery> for EventFilter {
+    fn from(query: EventQuery) -> Self {
+        let list = |values: Option<String>| -> Vec<String> {
+            values
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        EventFilter {
+            names: list(query.names),
+            device_urls: list(query.device_urls),
+        }
+    }
+}
+
+/// Simplified HTTP API for the gateway, devices are addressed by label or device URL:
+///
+/// - `GET /devices`, `GET /devices/{device}`
+/// - `POST /devices/{device}/{command}` with an optional `{"parameters": [..]}` body
+/// - `GET /executions`, `DELETE /executions/{id}`
+/// - `GET /events` as server-sent events, if an `EventHub` is given
+pub fn router(api: ApiClient, events: Option<EventHub>, config: &ProxyConfig) -> Router {
+    let api_keys = Arc::new(config.api_keys.clone());
+
+    let mut router = Router::new()
+        .route("/devices", get(list_devices))
+        .route("/devices/{device}", get(get_device))
+        .route("/devices/{device}/{command}", post(run_command))
+        .route("/executions", get(list_executions))
+        .route("/executions/{id}", delete(cancel_execution))
+        .with_state(api);
+    if let Some(hub) = events {
+        router = router.merge(
+            Router::new()
+                .route("/events", get(stream_events))
+                .with_state(hub),
+        );
+    }
+
+    router
+        .layer(middleware::from_fn_with_state(api_keys, authorize))
+        .layer(cors(&config.cors_origins))
+}
+
+async fn list_devices(State(api): State<ApiClient>) -> Result<Json<Vec<DeviceView>>, ProxyError> {
+    let devices = api.get_devices().await?;
+    Ok(Json(devices.into_iter().map(DeviceView::from).collect()))
+}
+
+async fn get_device(
+    State(api): State<ApiClient>,
+    Path(device): Path<String>,
+) -> Result<Json<DeviceView>, ProxyError> {
+    let device = find_device(api.get_devices().await?, &device)?;
+    Ok(Json(device.into()))
+}
+
+async fn run_command(
+    State(api): State<ApiClient>,
+    Path((device, command)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<Json<ExecutionStarted>, ProxyError> {
+    // An empty body runs the command without parameters
+    let body: CommandBody = if body.is_empty() {
+        CommandBody::default()
+    } else {
+        serde_json::from_slice(&body).map_err(ProxyError::InvalidBody)?
+    };
+    let device = find_device(api.get_devices().await?, &device)?;
+
+    let action_group = ActionGroup {
+        label: Some(format!("somfy-proxy: {command}")),
+        actions: vec![Action {
+            device_url: device.device_url,
+            commands: vec![Command {
+                name: command,
+                parameters: body
+                    .parameters
+                    .into_iter()
+                    .map(|p| match p {
+                        serde_json::Value::String(s) => s,
+                        other => other.to_string(),
+                    })
+                    .collect(),
+            }],
+        }],
+    };
+    let execution = api.execute_actions(&action_group).await?;
+    Ok(Json(ExecutionStarted {
+        exec_id: execution.exec_id,
+    }))
+}
+
+async fn list_executions(
+    State(api): State<ApiClient>,
+) -> Result<Json<Vec<ExecutionView>>, ProxyError> {
+    let executions = api.get_current_executions().await?;
+    Ok(Json(
+        executions.into_iter().map(ExecutionView::from).collect(),
+    ))
+}
+
+async fn cancel_execution(
+    State(api): State<ApiClient>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, ProxyError> {
+    api.cancel_execution(&id).await?;
+    Ok(Json(serde_json::json!({ "cancelled": id })))
+}
+
+async fn stream_events(
+    State(hub): State<EventHub>,
+    Query(query): Query<EventQuery>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let subscription = hub.subscribe(query.into());
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.recv().await?;
+        let sse = SseEvent::default()
+            .event(event.name.as_str())
+            .json_data(event.as_ref())
+            .unwrap_or_default();
+        Some((Ok(sse), subscription))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+fn find_device(devices: Vec<Device>, device: &str) -> Result<Device, ProxyError> {
+    let mut matches: Vec<Device> = devices
+        .into_iter()
+        .filter(|d| d.device_url == device || d.label.eq_ignore_ascii_case(device))
+        .collect();
+    match matches.len() {
+        0 => Err(ProxyError::DeviceNotFound(device.to_string())),
+        1 => Ok(matches.remove(0)),
+        _ => Err(ProxyError::AmbiguousLabel(device.to_string())),
+    }
+}
+
+async fn authorize(
+    State(api_keys): State<Arc<Vec<String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ProxyError> {
+    let headers = request.headers();
+    let presented = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()));
+
+    match presented {
+        Some(key) if api_keys.iter().any(|k| constant_time_eq(k, key)) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(ProxyError::Unauthorized),
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+fn cors(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|o| HeaderValue::from_str(o).ok()))
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            AUTHORIZATION,
+            CONTENT_TYPE,
+            HeaderName::from_static(API_KEY_HEADER),
+        ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::{ApiClientConfig, CertificateHandling, HttpProtocol};
+    use reqwest::StatusCode;
+
+    // Serves the proxy in front of a gateway that refuses connections
+    async fn serve() -> String {
+        serve_with_events(None).await
+    }
+
+    async fn serve_with_events(events: Option<EventHub>) -> String {
+        let api = ApiClient::new(ApiClientConfig {
+            protocol: HttpProtocol::HTTP,
+            port: 1,
+            url: "127.0.0.1".to_string(),
+            api_key: "gateway_key".to_string(),
+            cert_handling: CertificateHandling::NoCustomCert,
+            tls_server_name: None,
+        })
+        .await
+        .expect("should create an ApiClient");
+        let config = ProxyConfig {
+            api_keys: vec!["proxy_key".to_string()],
+            cors_origins: vec!["http://localhost:3000".to_string()],
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("should bind");
+        let addr = listener.local_addr().expect("should have address");
+        tokio::spawn(async move { axum::serve(listener, router(api, events, &config)).await });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_without_valid_api_key() {
+        let base = serve().await;
+        let client = reqwest::Client::new();
+
+        let resp = client.get(format!("{base}/devices")).send().await;
+        assert_eq!(
+            resp.expect("should respond").status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let resp = client
+            .get(format!("{base}/devices"))
+            .bearer_auth("other_key")
+            .send()
+            .await;
+        assert_eq!(
+            resp.expect("should respond").status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn forwards_authorized_requests_to_gateway() {
+        let base = serve().await;
+        let resp = reqwest::Client::new()
+            .get(format!("{base}/executions"))
+            .header(API_KEY_HEADER, "proxy_key")
+            .send()
+            .await
+            .expect("should respond");
+
+        // The gateway is unreachable, so passing authorization surfaces as a gateway error
+        assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+        let body: serde_json::Value = resp.json().await.expect("should be json");
+        assert!(body["error"].as_str().is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_command_bodies() {
+        let base = serve().await;
+        let resp = reqwest::Client::new()
+            .post(format!("{base}/devices/Office/open"))
+            .bearer_auth("proxy_key")
+            .body(r#"{"parameters": [1"#)
+            .send()
+            .await
+            .expect("should respond");
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn answers_cors_preflight_for_allowed_origins() {
+        let base = serve().await;
+        let resp = reqwest::Client::new()
+            .request(reqwest::Method::OPTIONS, format!("{base}/devices"))
+            .header("origin", "http://localhost:3000")
+            .header("access-control-request-method", "GET")
+            .send()
+            .await
+            .expect("should respond");
+
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers()
+                .get("access-control-allow-origin")
+                .map(|v| v.as_bytes()),
+            Some("http://localhost:3000".as_bytes())
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_filtered_events() {
+        let hub = EventHub::new(16);
+        let base = serve_with_events(Some(hub.clone())).await;
+        let mut resp = reqwest::Client::new()
+            .get(format!("{base}/events?device_urls=io://1/2"))
+            .bearer_auth("proxy_key")
+            .send()
+            .await
+            .expect("should respond");
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let event = |device_url: &str| {
+            serde_json::from_value(serde_json::json!({
+                "name": "DeviceStateChangedEvent",
+                "deviceURL": device_url,
+            }))
+            .expect("should build event")
+        };
+        hub.publish(event("io://1/1"));
+        hub.publish(event("io://1/2"));
+
+        let chunk = resp
+            .chunk()
+            .await
+            .expect("should read")
+            .expect("should have data");
+        let chunk = String::from_utf8_lossy(&chunk);
+        assert!(chunk.starts_with("event: DeviceStateChangedEvent\n"));
+        assert!(chunk.contains(r#""deviceURL":"io://1/2""#));
+    }
+
+    #[test]
+    fn finds_devices_by_label_or_url() {
+        let devices: Vec<Device> = serde_json::from_str(
+            r#"[
+            {"deviceURL": "io://1/1", "label": "Office", "controllableName": "io:Shutter",
+             "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+             "states": [], "attributes": []},
+            {"deviceURL": "io://1/2", "label": "Hall", "controllableName": "io:Shutter",
+             "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+             "states": [], "attributes": []},
+            {"deviceURL": "io://1/3", "label": "hall", "controllableName": "io:Shutter",
+             "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+             "states": [], "attributes": []}
+        ]"#,
+        )
+        .expect("should parse devices");
+
+        let found = find_device(devices.clone(), "office").expect("should find by label");
+        assert_eq!(found.device_url, "io://1/1");
+        let found = find_device(devices.clone(), "io://1/3").expect("should find by url");
+        assert_eq!(found.label, "hall");
+        assert!(matches!(
+            find_device(devices.clone(), "Hall"),
+            Err(ProxyError::AmbiguousLabel(_))
+        ));
+        assert!(matches!(
+            find_device(devices, "Kitchen"),
+            Err(ProxyError::DeviceNotFound(_))
+        ));
+    }
+}
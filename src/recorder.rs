@@ -0,0 +1,367 @@
+use crate::commands::types::{
+    Device, DeviceStateValue, Event, DEVICE_STATE_CHANGED_EVENT, EXECUTION_STATE_CHANGED_EVENT,
+};
+use crate::device_url::DeviceUrl;
+use crate::err::recorder::RecorderError;
+#[cfg(feature = "client")]
+use crate::events::{EventFilter, EventHub};
+use crate::util::now;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateRecord {
+    /// Milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub device_url: DeviceUrl,
+    pub state: String,
+    /// `None` for the first value seen of a state
+    pub old: Option<DeviceStateValue>,
+    pub new: DeviceStateValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionRecord {
+    /// Milliseconds since the unix epoch
+    pub timestamp: i64,
+    pub exec_id: String,
+    pub old_state: Option<String>,
+    pub new_state: String,
+    pub failure_type: Option<String>,
+}
+
+/// One line of the history file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record {
+    State(StateRecord),
+    Execution(ExecutionRecord),
+}
+
+/// Appends state changes and execution outcomes to a JSONL file and queries them.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    file: File,
+    // Last value per device URL and state name, to record old values
+    last: HashMap<(DeviceUrl, String), DeviceStateValue>,
+}
+
+impl Recorder {
+    /// Opens or creates the history file at `path`. Old values are taken from the existing history.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RecorderError> {
+        let path = path.as_ref().to_path_buf();
+        truncate_partial_line(&path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|source| RecorderError::Io {
+                path: path.clone(),
+                source,
+            })?;
+
+        let mut recorder = Self {
+            path,
+            file,
+            last: HashMap::new(),
+        };
+        for record in recorder.records()? {
+            if let Record::State(state) = record {
+                recorder
+                    .last
+                    .insert((state.device_url, state.state), state.new);
+            }
+        }
+        Ok(recorder)
+    }
+
+    /// Sets the known values of `devices` without recording them, e.g. from `get_setup`.
+    pub fn seed<'a>(&mut self, devices: impl IntoIterator<Item = &'a Device>) {
+        for device in devices {
+            for state in &device.states {
+                self.last.insert(
+                    (device.device_url.clone(), state.name.clone()),
+                    state.value.clone(),
+                );
+            }
+        }
+    }
+
+    /// Records the state changes or execution outcome of `event`, other events are ignored.
+    /// Returns the appended records.
+    pub fn record(&mut self, event: &Event) -> Result<Vec<Record>, RecorderError> {
+        let timestamp = event.timestamp.unwrap_or_else(now);
+        let records: Vec<Record> = match (event.name.as_str(), &event.device_url, &event.exec_id) {
+            (DEVICE_STATE_CHANGED_EVENT, Some(device_url), _) => event
+                .device_states
+                .iter()
+                .map(|state| {
+                    let key = (device_url.clone(), state.name.clone());
+                    Record::State(StateRecord {
+                        timestamp,
+                        device_url: device_url.clone(),
+                        state: state.name.clone(),
+                        old: self.last.insert(key, state.value.clone()),
+                        new: state.value.clone(),
+                    })
+                })
+                .collect(),
+            (EXECUTION_STATE_CHANGED_EVENT, _, Some(exec_id)) => event
+                .new_state
+                .iter()
+                .map(|new_state| {
+                    Record::Execution(ExecutionRecord {
+                        timestamp,
+                        exec_id: exec_id.clone(),
+                        old_state: event.old_state.clone(),
+                        new_state: new_state.clone(),
+                        failure_type: event.failure_type.clone(),
+                    })
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        for record in &records {
+            self.append(record)?;
+        }
+        Ok(records)
+    }
+
+    /// Records events of `hub` until it is dropped.
+    #[cfg(feature = "client")]
+    pub async fn follow(&mut self, hub: &EventHub) -> Result<(), RecorderError> {
+        let mut events = hub.subscribe(EventFilter {
+            names: vec![
+                DEVICE_STATE_CHANGED_EVENT.to_string(),
+                EXECUTION_STATE_CHANGED_EVENT.to_string(),
+            ],
+            ..EventFilter::default()
+        });
+        while let Some(event) = events.recv().await {
+            self.record(&event)?;
+        }
+        Ok(())
+    }
+
+    /// All values of `state` of a device with timestamps in `range`, oldest first.
+    pub fn history(
+        &self,
+        device_url: &str,
+        state: &str,
+        range: impl RangeBounds<i64>,
+    ) -> Result<Vec<StateRecord>, RecorderError> {
+        Ok(self
+            .records()?
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::State(s)
+                    if s.device_url == device_url
+                        && s.state == state
+                        && range.contains(&s.timestamp) =>
+                {
+                    Some(s)
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// All execution state changes with timestamps in `range`, oldest first.
+    pub fn executions(
+        &self,
+        range: impl RangeBounds<i64>,
+    ) -> Result<Vec<ExecutionRecord>, RecorderError> {
+        Ok(self
+            .records()?
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Execution(e) if range.contains(&e.timestamp) => Some(e),
+                _ => None,
+            })
+            .collect())
+    }
+
+    pub fn records(&self) -> Result<Vec<Record>, RecorderError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(source) => return Err(self.io_error(source)),
+        };
+
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|source| self.io_error(source))?;
+        let mut records = vec![];
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                // A write still in progress, or interrupted before `open` truncated it
+                Err(e) if index + 1 == lines.len() => {
+                    debug!("Skipping partial last line of {:?}: {e}", self.path)
+                }
+                Err(source) => {
+                    return Err(RecorderError::InvalidRecord {
+                        path: self.path.clone(),
+                        line: index + 1,
+                        source,
+                    })
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn append(&mut self, record: &Record) -> Result<(), RecorderError> {
+        // Serializing our own records cannot fail
+        let mut line = serde_json::to_string(record).unwrap_or_default();
+        line.push('\n');
+        // A single write per line so an interrupted process leaves at most one partial line
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|source| self.io_error(source))
+    }
+
+    fn io_error(&self, source: std::io::Error) -> RecorderError {
+        RecorderError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+// Cuts off a last line left partial by an interrupted write, so appends start on a new line
+fn truncate_partial_line(path: &Path) -> Result<(), RecorderError> {
+    let io_error = |source| RecorderError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(source) => return Err(io_error(source)),
+    };
+    if content.is_empty() || content.ends_with(b"\n") {
+        return Ok(());
+    }
+
+    let last_start = content
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    if serde_json::from_slice::<Record>(&content[last_start..]).is_ok() {
+        // Complete record that only misses its newline
+        return OpenOptions::new()
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(b"\n"))
+            .map_err(io_error);
+    }
+    warn!("Truncating partial last line of {path:?}");
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(last_start as u64))
+        .map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(value: serde_json::Value) -> Event {
+        serde_json::from_value(value).expect("should parse event")
+    }
+
+    fn closure(timestamp: i64, value: i64) -> Event {
+        event(serde_json::json!({
+            "name": "DeviceStateChangedEvent",
+            "deviceURL": "io://1/1",
+            "timestamp": timestamp,
+            "deviceStates": [{"name": "core:ClosureState", "type": 1, "value": value}],
+        }))
+    }
+
+    #[test]
+    fn records_and_queries_history() {
+        let path =
+            std::env::temp_dir().join(format!("somfy_sdk_{}_history.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut recorder = Recorder::open(&path).expect("should open");
+        recorder.record(&closure(1000, 0)).expect("should record");
+        recorder.record(&closure(2000, 100)).expect("should record");
+        recorder
+            .record(&event(serde_json::json!({
+                "name": "ExecutionStateChangedEvent",
+                "execId": "exec-1",
+                "timestamp": 2500,
+                "oldState": "IN_PROGRESS",
+                "newState": "FAILED",
+                "failureType": "CMDCANCELLED",
+            })))
+            .expect("should record");
+
+        // Reopening picks up the last known values
+        let mut recorder = Recorder::open(&path).expect("should reopen");
+        recorder.record(&closure(3000, 50)).expect("should record");
+
+        let history = recorder
+            .history("io://1/1", "core:ClosureState", 1500..)
+            .expect("should query");
+        let values: Vec<_> = history
+            .iter()
+            .map(|r| (r.old.clone(), r.new.clone()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (Some(DeviceStateValue::Int(0)), DeviceStateValue::Int(100)),
+                (Some(DeviceStateValue::Int(100)), DeviceStateValue::Int(50)),
+            ]
+        );
+
+        let executions = recorder.executions(..).expect("should query");
+        assert_eq!(executions.len(), 1);
+        assert_eq!(executions[0].failure_type.as_deref(), Some("CMDCANCELLED"));
+
+        std::fs::remove_file(&path).expect("should clean up");
+    }
+
+    #[test]
+    fn ignores_partial_last_line() {
+        let path =
+            std::env::temp_dir().join(format!("somfy_sdk_{}_partial.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut recorder = Recorder::open(&path).expect("should open");
+        recorder.record(&closure(1000, 100)).expect("should record");
+
+        // An interrupted write
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(br#"{"kind":"sta"#))
+            .expect("should append");
+        assert_eq!(recorder.records().expect("should skip").len(), 1);
+
+        let mut recorder = Recorder::open(&path).expect("should reopen");
+        recorder.record(&closure(2000, 50)).expect("should record");
+        let history = recorder
+            .history("io://1/1", "core:ClosureState", ..)
+            .expect("should query");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].old, Some(DeviceStateValue::Int(100)));
+
+        std::fs::remove_file(&path).expect("should clean up");
+    }
+}
@@ -0,0 +1,848 @@
+use crate::api_client::{ApiClientConfig, CertificateHandling, HttpProtocol};
+use crate::commands::types::{
+    ActionGroup, ActionGroupExecution, Device, DeviceState, DeviceStateValue, Event, Setup,
+    DEVICE_STATE_CHANGED_EVENT, EXECUTION_STATE_CHANGED_EVENT,
+};
+use crate::device_url::DeviceUrl;
+use crate::util::now;
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+const CLOSURE_STATE: &str = "core:ClosureState";
+const OPEN_CLOSED_STATE: &str = "core:OpenClosedState";
+const MOVING_STATE: &str = "core:MovingState";
+const ON_OFF_STATE: &str = "core:OnOffState";
+const LIGHT_INTENSITY_STATE: &str = "core:LightIntensityState";
+const EXECUTION_REGISTERED_EVENT: &str = "ExecutionRegisteredEvent";
+const DEVICE_AVAILABLE_EVENT: &str = "DeviceAvailableEvent";
+const DEVICE_UNAVAILABLE_EVENT: &str = "DeviceUnavailableEvent";
+// Closure the `my` command moves to
+const MY_POSITION: i64 = 50;
+
+#[derive(Debug, Clone)]
+pub struct SimulatorConfig {
+    /// Token clients have to send as `Authorization: Bearer <api_key>`
+    pub api_key: String,
+    /// How often executions and moving devices progress
+    pub tick: Duration,
+    /// Closure change per tick of a moving device, in percent
+    pub closure_step: i64,
+    pub setup: Setup,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            api_key: "simulator".to_string(),
+            tick: Duration::from_millis(50),
+            closure_step: 25,
+            setup: default_setup(),
+        }
+    }
+}
+
+/// An in-process gateway serving the local API over HTTP on a random port, for tests.
+///
+/// Executions progress from `IN_PROGRESS` to `COMPLETED` once all their devices stopped
+/// moving, devices with a `core:ClosureState` move by `closure_step` per tick, and every
+/// registered listener gets its own event queue. The server stops when dropped.
+#[derive(Debug)]
+pub struct Simulator {
+    addr: SocketAddr,
+    api_key: String,
+    world: Arc<Mutex<World>>,
+    tasks: [JoinHandle<()>; 2],
+}
+
+impl Simulator {
+    pub async fn start() -> std::io::Result<Self> {
+        Self::with_config(SimulatorConfig::default()).await
+    }
+
+    pub async fn with_config(config: SimulatorConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let world = Arc::new(Mutex::new(World::new(config.setup, config.closure_step)));
+
+        let app = router(world.clone(), config.api_key.clone());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        let ticker = tokio::spawn({
+            let world = world.clone();
+            async move {
+                let mut tick = tokio::time::interval(config.tick);
+                loop {
+                    tick.tick().await;
+                    lock(&world).tick();
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            api_key: config.api_key,
+            world,
+            tasks: [server, ticker],
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Config for an `ApiClient` talking to this simulator.
+    pub fn client_config(&self) -> ApiClientConfig {
+        ApiClientConfig {
+            cert_handling: CertificateHandling::NoCustomCert,
+            protocol: HttpProtocol::HTTP,
+            url: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            api_key: self.api_key.clone(),
+            tls_server_name: None,
+        }
+    }
+
+    /// The current setup, including all state changes.
+    pub fn setup(&self) -> Setup {
+        lock(&self.world).setup.clone()
+    }
+
+    /// Sets a state of a device and sends a `DeviceStateChangedEvent`.
+    pub fn set_state(&self, device_url: &str, state: DeviceState) {
+        lock(&self.world).set_states(device_url, vec![state]);
+    }
+
+    /// Makes a device (un)available. Executions on unavailable devices fail.
+    pub fn set_available(&self, device_url: &str, available: bool) {
+        let mut world = lock(&self.world);
+        if let Some(device) = world.device_mut(device_url) {
+            device.available = available;
+            let name = if available {
+                DEVICE_AVAILABLE_EVENT
+            } else {
+                DEVICE_UNAVAILABLE_EVENT
+            };
+            world.publish(event(name, Some(device_url), json!({})));
+        }
+    }
+
+    /// Adds an event to the queue of every registered listener.
+    pub fn push_event(&self, event: Event) {
+        lock(&self.world).publish(event);
+    }
+
+    /// Answers the next `times` requests whose path contains `path` with `status`.
+    /// An empty `path` matches every request.
+    pub fn fail_requests(&self, path: &str, status: StatusCode, times: usize) {
+        lock(&self.world).failures.push(Failure {
+            path: path.to_string(),
+            status,
+            remaining: times,
+        });
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.tasks.iter().for_each(JoinHandle::abort);
+    }
+}
+
+#[derive(Debug)]
+struct Failure {
+    path: String,
+    status: StatusCode,
+    remaining: usize,
+}
+
+#[derive(Debug)]
+struct Motion {
+    exec_id: String,
+    target: i64,
+}
+
+#[derive(Debug)]
+struct Execution {
+    execution: ActionGroupExecution,
+    failure: Option<&'static str>,
+}
+
+#[derive(Debug)]
+struct World {
+    setup: Setup,
+    closure_step: i64,
+    executions: Vec<Execution>,
+    motions: HashMap<String, Motion>,
+    listeners: HashMap<String, Vec<Event>>,
+    failures: Vec<Failure>,
+    next_id: u64,
+}
+
+impl World {
+    fn new(setup: Setup, closure_step: i64) -> Self {
+        Self {
+            setup,
+            closure_step: closure_step.max(1),
+            executions: vec![],
+            motions: HashMap::new(),
+            listeners: HashMap::new(),
+            failures: vec![],
+            next_id: 1,
+        }
+    }
+
+    fn id(&mut self) -> String {
+        self.next_id += 1;
+        format!("{:08x}-0000-4000-8000-{:012x}", self.next_id, now())
+    }
+
+    fn device(&self, device_url: &str) -> Option<&Device> {
+        self.setup
+            .devices
+            .iter()
+            .find(|d| d.device_url == device_url)
+    }
+
+    fn device_mut(&mut self, device_url: &str) -> Option<&mut Device> {
+        self.setup
+            .devices
+            .iter_mut()
+            .find(|d| d.device_url == device_url)
+    }
+
+    fn publish(&mut self, event: Event) {
+        for queue in self.listeners.values_mut() {
+            queue.push(event.clone());
+        }
+    }
+
+    fn set_states(&mut self, device_url: &str, states: Vec<DeviceState>) {
+        let Some(device) = self.device_mut(device_url) else {
+            return;
+        };
+        for changed in &states {
+            match device.states.iter_mut().find(|s| s.name == changed.name) {
+                Some(state) => state.value = changed.value.clone(),
+                None => device.states.push(changed.clone()),
+            }
+        }
+        self.publish(event(
+            DEVICE_STATE_CHANGED_EVENT,
+            Some(device_url),
+            json!({ "deviceStates": states }),
+        ));
+    }
+
+    // Fails with the first unknown device URL
+    fn apply(&mut self, action_group: ActionGroup) -> Result<String, DeviceUrl> {
+        if let Some(action) = action_group
+            .actions
+            .iter()
+            .find(|a| self.device(&a.device_url).is_none())
+        {
+            return Err(action.device_url.clone());
+        }
+
+        let exec_id = self.id();
+        let mut failure = None;
+        for action in &action_group.actions {
+            for command in &action.commands {
+                let Some(device) = self.device(&action.device_url) else {
+                    continue;
+                };
+                if !device.available {
+                    failure = Some("DEVICE_UNAVAILABLE");
+                } else if !device.supports_command(&command.name) {
+                    failure = Some("CMD_NOT_SUPPORTED");
+                } else {
+                    self.run_command(&exec_id, &action.device_url, command);
+                }
+            }
+        }
+
+        let execution = ActionGroupExecution {
+            owner: "simulator".to_string(),
+            id: exec_id.clone(),
+            execution_type: "Immediate execution".to_string(),
+            execution_sub_type: "MANUAL_CONTROL".to_string(),
+            description: "Execution from the local API".to_string(),
+            start_time: now(),
+            action_group,
+            state: "IN_PROGRESS".to_string(),
+        };
+        self.publish(event(
+            EXECUTION_REGISTERED_EVENT,
+            None,
+            json!({ "execId": exec_id }),
+        ));
+        self.publish(execution_event(
+            &exec_id,
+            "INITIALIZED",
+            "IN_PROGRESS",
+            None,
+        ));
+        self.executions.push(Execution { execution, failure });
+        Ok(exec_id)
+    }
+
+    fn run_command(
+        &mut self,
+        exec_id: &str,
+        device_url: &str,
+        command: &crate::commands::types::Command,
+    ) {
+        let param = command
+            .parameters
+            .first()
+            .and_then(|p| p.parse::<i64>().ok());
+        let target = match (command.name.as_str(), param) {
+            ("open", _) => Some(0),
+            ("close", _) => Some(100),
+            ("my", _) => Some(MY_POSITION),
+            ("setClosure", Some(closure)) => Some(closure.clamp(0, 100)),
+            ("stop", _) => {
+                if self.motions.remove(device_url).is_some() {
+                    self.set_states(
+                        device_url,
+                        vec![state(MOVING_STATE, 6, DeviceStateValue::Boolean(false))],
+                    );
+                }
+                None
+            }
+            ("on" | "off", _) => {
+                let value = DeviceStateValue::String(command.name.clone());
+                self.set_states(device_url, vec![state(ON_OFF_STATE, 3, value)]);
+                None
+            }
+            ("setIntensity", Some(intensity)) => {
+                let value = DeviceStateValue::Int(intensity.clamp(0, 100));
+                self.set_states(device_url, vec![state(LIGHT_INTENSITY_STATE, 1, value)]);
+                None
+            }
+            _ => None,
+        };
+
+        let has_closure = self
+            .device(device_url)
+            .is_some_and(|d| d.state(CLOSURE_STATE).is_some());
+        if let (Some(target), true) = (target, has_closure) {
+            self.motions.insert(
+                device_url.to_string(),
+                Motion {
+                    exec_id: exec_id.to_string(),
+                    target,
+                },
+            );
+            self.set_states(
+                device_url,
+                vec![state(MOVING_STATE, 6, DeviceStateValue::Boolean(true))],
+            );
+        }
+    }
+
+    fn cancel(&mut self, exec_id: &str) -> bool {
+        let Some(index) = self
+            .executions
+            .iter()
+            .position(|e| e.execution.id == exec_id)
+        else {
+            return false;
+        };
+        self.executions.remove(index);
+
+        let stopped: Vec<String> = self
+            .motions
+            .iter()
+            .filter(|(_, m)| m.exec_id == exec_id)
+            .map(|(url, _)| url.clone())
+            .collect();
+        for device_url in stopped {
+            self.motions.remove(&device_url);
+            self.set_states(
+                &device_url,
+                vec![state(MOVING_STATE, 6, DeviceStateValue::Boolean(false))],
+            );
+        }
+        self.publish(execution_event(
+            exec_id,
+            "IN_PROGRESS",
+            "FAILED",
+            Some("CMDCANCELLED"),
+        ));
+        true
+    }
+
+    fn tick(&mut self) {
+        let mut urls: Vec<String> = self.motions.keys().cloned().collect();
+        urls.sort();
+        for device_url in urls {
+            let Some(target) = self.motions.get(&device_url).map(|m| m.target) else {
+                continue;
+            };
+            let current = match self
+                .device(&device_url)
+                .and_then(|d| d.state(CLOSURE_STATE))
+            {
+                Some(DeviceStateValue::Int(closure)) => *closure,
+                _ => target,
+            };
+            let next = if current < target {
+                (current + self.closure_step).min(target)
+            } else {
+                (current - self.closure_step).max(target)
+            };
+
+            let open_closed = if next == 100 { "closed" } else { "open" };
+            let mut states = vec![
+                state(CLOSURE_STATE, 1, DeviceStateValue::Int(next)),
+                state(
+                    OPEN_CLOSED_STATE,
+                    3,
+                    DeviceStateValue::String(open_closed.to_string()),
+                ),
+            ];
+            if next == target {
+                self.motions.remove(&device_url);
+                states.push(state(MOVING_STATE, 6, DeviceStateValue::Boolean(false)));
+            }
+            self.set_states(&device_url, states);
+        }
+
+        // Executions end once none of their devices are moving anymore
+        let (done, running): (Vec<_>, Vec<_>) = std::mem::take(&mut self.executions)
+            .into_iter()
+            .partition(|e| {
+                e.failure.is_some() || !self.motions.values().any(|m| m.exec_id == e.execution.id)
+            });
+        self.executions = running;
+        for execution in done {
+            let new_state = if execution.failure.is_some() {
+                "FAILED"
+            } else {
+                "COMPLETED"
+            };
+            self.publish(execution_event(
+                &execution.execution.id,
+                "IN_PROGRESS",
+                new_state,
+                execution.failure,
+            ));
+        }
+    }
+}
+
+fn router(world: Arc<Mutex<World>>, api_key: String) -> Router {
+    let api = Router::new()
+        .route("/apiVersion", get(api_version))
+        .route("/setup", get(setup))
+        .route("/setup/gateways", get(gateways))
+        .route("/setup/devices", get(devices))
+        .route("/setup/devices/{device}", get(device))
+        .route("/setup/devices/{device}/states", get(device_states))
+        .route("/setup/devices/{device}/states/{name}", get(device_state))
+        .route(
+            "/setup/devices/controllables/{controllable}",
+            get(devices_by_controllable),
+        )
+        .route("/events/register", post(register_listener))
+        .route("/events/{id}/fetch", post(fetch_events))
+        .route("/events/{id}/unregister", post(unregister_listener))
+        .route("/exec/apply", post(apply))
+        .route("/exec/current", get(executions))
+        .route("/exec/current/{id}", get(execution))
+        .route("/exec/current/setup", delete(cancel_all))
+        .route("/exec/current/setup/{id}", delete(cancel))
+        .with_state(world.clone());
+
+    Router::new()
+        .nest("/enduser-mobile-web/1/enduserAPI", api)
+        .layer(middleware::from_fn_with_state(world, inject_failures))
+        .layer(middleware::from_fn_with_state(Arc::new(api_key), authorize))
+}
+
+async fn authorize(State(api_key): State<Arc<String>>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|token| token == api_key.as_str());
+    if !authorized {
+        return error(StatusCode::UNAUTHORIZED, "Missing or invalid token");
+    }
+    next.run(request).await
+}
+
+async fn inject_failures(
+    State(world): State<Arc<Mutex<World>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let status = {
+        let mut world = lock(&world);
+        let path = request.uri().path();
+        let failure = world
+            .failures
+            .iter_mut()
+            .find(|f| f.remaining > 0 && path.contains(&f.path));
+        failure.map(|f| {
+            f.remaining -= 1;
+            f.status
+        })
+    };
+    match status {
+        Some(status) => error(status, "Injected failure"),
+        None => next.run(request).await,
+    }
+}
+
+type Shared = State<Arc<Mutex<World>>>;
+
+async fn api_version() -> Json<Value> {
+    Json(json!({ "protocolVersion": "2025.3.2-7" }))
+}
+
+async fn setup(State(world): Shared) -> Json<Setup> {
+    Json(lock(&world).setup.clone())
+}
+
+async fn gateways(State(world): Shared) -> Response {
+    Json(lock(&world).setup.gateways.clone()).into_response()
+}
+
+async fn devices(State(world): Shared) -> Response {
+    Json(lock(&world).setup.devices.clone()).into_response()
+}
+
+async fn device(State(world): Shared, Path(device_url): Path<String>) -> Response {
+    match lock(&world).device(&device_url) {
+        Some(device) => Json(device.clone()).into_response(),
+        None => unknown_device(&device_url),
+    }
+}
+
+async fn device_states(State(world): Shared, Path(device_url): Path<String>) -> Response {
+    match lock(&world).device(&device_url) {
+        Some(device) => Json(device.states.clone()).into_response(),
+        None => unknown_device(&device_url),
+    }
+}
+
+async fn device_state(
+    State(world): Shared,
+    Path((device_url, name)): Path<(String, String)>,
+) -> Response {
+    let world = lock(&world);
+    let Some(device) = world.device(&device_url) else {
+        return unknown_device(&device_url);
+    };
+    match device.states.iter().find(|s| s.name == name) {
+        Some(state) => Json(state.clone()).into_response(),
+        None => error(StatusCode::NOT_FOUND, &format!("Unknown state {name}")),
+    }
+}
+
+async fn devices_by_controllable(
+    State(world): Shared,
+    Path(controllable): Path<String>,
+) -> Json<Vec<DeviceUrl>> {
+    let world = lock(&world);
+    Json(
+        world
+            .setup
+            .devices
+            .iter()
+            .filter(|d| d.controllable_name == controllable)
+            .map(|d| d.device_url.clone())
+            .collect(),
+    )
+}
+
+async fn register_listener(State(world): Shared) -> Json<Value> {
+    let mut world = lock(&world);
+    let id = world.id();
+    world.listeners.insert(id.clone(), vec![]);
+    Json(json!({ "id": id }))
+}
+
+async fn fetch_events(State(world): Shared, Path(id): Path<String>) -> Response {
+    match lock(&world).listeners.get_mut(&id) {
+        Some(queue) => Json(std::mem::take(queue)).into_response(),
+        None => unknown_listener(&id),
+    }
+}
+
+async fn unregister_listener(State(world): Shared, Path(id): Path<String>) -> Response {
+    match lock(&world).listeners.remove(&id) {
+        Some(_) => Json(json!([])).into_response(),
+        None => unknown_listener(&id),
+    }
+}
+
+async fn apply(State(world): Shared, Json(action_group): Json<ActionGroup>) -> Response {
+    match lock(&world).apply(action_group) {
+        Ok(exec_id) => Json(json!({ "execId": exec_id })).into_response(),
+        Err(device_url) => error(
+            StatusCode::BAD_REQUEST,
+            &format!("Unknown device {device_url}"),
+        ),
+    }
+}
+
+async fn executions(State(world): Shared) -> Json<Vec<ActionGroupExecution>> {
+    let world = lock(&world);
+    Json(
+        world
+            .executions
+            .iter()
+            .map(|e| e.execution.clone())
+            .collect(),
+    )
+}
+
+async fn execution(State(world): Shared, Path(id): Path<String>) -> Response {
+    let world = lock(&world);
+    match world.executions.iter().find(|e| e.execution.id == id) {
+        Some(e) => Json(e.execution.clone()).into_response(),
+        None => error(StatusCode::NOT_FOUND, &format!("Unknown execution {id}")),
+    }
+}
+
+async fn cancel_all(State(world): Shared) -> Json<Value> {
+    let mut world = lock(&world);
+    let ids: Vec<String> = world
+        .executions
+        .iter()
+        .map(|e| e.execution.id.clone())
+        .collect();
+    for id in ids {
+        world.cancel(&id);
+    }
+    Json(json!({}))
+}
+
+async fn cancel(State(world): Shared, Path(id): Path<String>) -> Response {
+    match lock(&world).cancel(&id) {
+        true => Json(json!({})).into_response(),
+        false => error(StatusCode::NOT_FOUND, &format!("Unknown execution {id}")),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({ "errorCode": "RESOURCE_ACCESS_DENIED", "error": message })),
+    )
+        .into_response()
+}
+
+fn unknown_device(device_url: &str) -> Response {
+    error(
+        StatusCode::NOT_FOUND,
+        &format!("Unknown device {device_url}"),
+    )
+}
+
+fn unknown_listener(id: &str) -> Response {
+    error(
+        StatusCode::BAD_REQUEST,
+        &format!("Unknown event listener {id}"),
+    )
+}
+
+// A panicking test thread must not take all other requests down with it
+fn lock(world: &Mutex<World>) -> MutexGuard<'_, World> {
+    world.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn state(name: &str, state_type: i64, value: DeviceStateValue) -> DeviceState {
+    DeviceState {
+        name: name.to_string(),
+        state_type,
+        value,
+    }
+}
+
+fn event(name: &str, device_url: Option<&str>, fields: Value) -> Event {
+    let mut event = json!({ "name": name, "deviceURL": device_url, "timestamp": now() });
+    if let (Value::Object(event), Value::Object(fields)) = (&mut event, fields) {
+        event.extend(fields);
+    }
+    // The fields above always form a valid event
+    serde_json::from_value(event).unwrap_or_else(|_| unreachable!())
+}
+
+fn execution_event(
+    exec_id: &str,
+    old_state: &str,
+    new_state: &str,
+    failure_type: Option<&str>,
+) -> Event {
+    event(
+        EXECUTION_STATE_CHANGED_EVENT,
+        None,
+        json!({
+            "execId": exec_id,
+            "oldState": old_state,
+            "newState": new_state,
+            "failureType": failure_type,
+        }),
+    )
+}
+
+/// A gateway with a roller shutter, an awning and a light.
+pub fn default_setup() -> Setup {
+    let device = |id: u32,
+                  label: &str,
+                  controllable: &str,
+                  ui_class: &str,
+                  widget: &str,
+                  commands: &[(&str, i64)],
+                  states: Value| {
+        let commands: Vec<Value> = commands
+            .iter()
+            .map(|(name, nparams)| json!({ "commandName": name, "nparams": nparams }))
+            .collect();
+        let state_names: Vec<Value> = states
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|s| json!({ "name": s["name"] }))
+            .collect();
+        json!({
+            "deviceURL": format!("io://0000-1111-2222/{id}"),
+            "label": label,
+            "controllableName": controllable,
+            "subsystemId": 0,
+            "type": 1,
+            "available": true,
+            "synced": true,
+            "enabled": true,
+            "states": states,
+            "attributes": [{ "name": "core:Manufacturer", "type": 3, "value": "Somfy" }],
+            "definition": {
+                "uiClass": ui_class,
+                "widgetName": widget,
+                "type": "ACTUATOR",
+                "attributes": [],
+                "states": state_names,
+                "commands": commands,
+            },
+        })
+    };
+    let covering = [
+        ("open", 0),
+        ("close", 0),
+        ("stop", 0),
+        ("my", 0),
+        ("setClosure", 1),
+    ];
+    let covering_states = |closure: i64| {
+        json!([
+            { "name": CLOSURE_STATE, "type": 1, "value": closure },
+            { "name": OPEN_CLOSED_STATE, "type": 3, "value": if closure == 100 { "closed" } else { "open" } },
+            { "name": MOVING_STATE, "type": 6, "value": false },
+        ])
+    };
+
+    let setup = json!({
+        "gateways": [{
+            "gatewayId": "0000-1111-2222",
+            "connectivity": { "status": "OK", "protocolVersion": "2025.3.2-7" },
+        }],
+        "devices": [
+            device(1, "Living Room", "io:RollerShutterGenericIOComponent", "RollerShutter",
+                "PositionableRollerShutter", &covering, covering_states(0)),
+            device(2, "Terrace", "io:HorizontalAwningIOComponent", "Awning",
+                "PositionableHorizontalAwning", &covering, covering_states(100)),
+            device(3, "Kitchen Light", "io:DimmableLightIOComponent", "Light", "DimmerLight",
+                &[("on", 0), ("off", 0), ("setIntensity", 1)],
+                json!([
+                    { "name": ON_OFF_STATE, "type": 3, "value": "off" },
+                    { "name": LIGHT_INTENSITY_STATE, "type": 1, "value": 0 },
+                ])),
+        ],
+    });
+    serde_json::from_value(setup).unwrap_or_else(|_| unreachable!())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::types::{Action, Command};
+
+    fn close(device_url: &str) -> ActionGroup {
+        ActionGroup {
+            label: None,
+            actions: vec![Action {
+                device_url: device_url.parse().expect("should parse"),
+                commands: vec![Command {
+                    name: "close".to_string(),
+                    parameters: vec![],
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn moves_devices_and_completes_executions() {
+        let mut world = World::new(default_setup(), 50);
+        world.listeners.insert("listener".to_string(), vec![]);
+        let exec_id = world
+            .apply(close("io://0000-1111-2222/1"))
+            .expect("should apply");
+
+        world.tick();
+        assert_eq!(world.executions.len(), 1);
+        world.tick();
+        assert!(world.executions.is_empty());
+
+        let device = world.device("io://0000-1111-2222/1").expect("should exist");
+        assert_eq!(
+            device.state(CLOSURE_STATE),
+            Some(&DeviceStateValue::Int(100))
+        );
+        assert_eq!(
+            device.state(MOVING_STATE),
+            Some(&DeviceStateValue::Boolean(false))
+        );
+
+        let events = &world.listeners["listener"];
+        let last = events.last().expect("should have events");
+        assert_eq!(last.exec_id.as_deref(), Some(exec_id.as_str()));
+        assert_eq!(last.new_state.as_deref(), Some("COMPLETED"));
+    }
+
+    #[test]
+    fn fails_executions_on_unavailable_devices() {
+        let mut world = World::new(default_setup(), 50);
+        world.listeners.insert("listener".to_string(), vec![]);
+        if let Some(device) = world.device_mut("io://0000-1111-2222/2") {
+            device.available = false;
+        }
+        world
+            .apply(close("io://0000-1111-2222/2"))
+            .expect("should apply");
+        world.tick();
+
+        let last = world.listeners["listener"]
+            .last()
+            .cloned()
+            .expect("should have events");
+        assert_eq!(last.new_state.as_deref(), Some("FAILED"));
+        assert_eq!(last.failure_type.as_deref(), Some("DEVICE_UNAVAILABLE"));
+        assert!(world.apply(close("io://0000-1111-2222/9")).is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use crate::commands::types::{
+    Device, DeviceAttributeValue, DeviceStateValue, Gateway, GatewayConnectivity, Setup,
+};
+use crate::device_url::DeviceUrl;
+use crate::err::snapshot::SnapshotError;
+use crate::util::now;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the snapshot format, other versions are rejected when loading.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A full `Setup` saved at a point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Milliseconds since the unix epoch
+    pub taken_at: i64,
+    pub setup: Setup,
+}
+
+impl Snapshot {
+    pub fn new(setup: Setup) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: now(),
+            setup,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        // Check the version first, a newer snapshot may not match the current structure
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let Version { version } = serde_json::from_str(json)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()?).map_err(|source| SnapshotError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|source| SnapshotError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_json(&json)
+    }
+
+    /// Changes from this snapshot to `newer`.
+    pub fn diff(&self, newer: &Snapshot) -> SetupDiff {
+        diff(&self.setup, &newer.setup)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SetupDiff {
+    pub gateways_added: Vec<Gateway>,
+    pub gateways_removed: Vec<Gateway>,
+    pub gateways_changed: Vec<GatewayChange>,
+    pub devices_added: Vec<Device>,
+    pub devices_removed: Vec<Device>,
+    pub devices_renamed: Vec<Renamed>,
+    pub states_changed: Vec<ValueChange<DeviceStateValue>>,
+    pub attributes_changed: Vec<ValueChange<DeviceAttributeValue>>,
+}
+
+impl SetupDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &SetupDiff::default()
+    }
+}
+
+/// A gateway whose connectivity status or protocol version changed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GatewayChange {
+    pub gateway_id: String,
+    pub old: GatewayConnectivity,
+    pub new: GatewayConnectivity,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Renamed {
+    pub device_url: DeviceUrl,
+    pub old_label: String,
+    pub new_label: String,
+}
+
+/// A state or attribute of a device that changed, was added (`old` is `None`) or was removed
+/// (`new` is `None`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueChange<T> {
+    pub device_url: DeviceUrl,
+    pub name: String,
+    pub old: Option<T>,
+    pub new: Option<T>,
+}
+
+/// Gateways are matched by id and devices by device URL, so a device paired again shows up as
+/// removed and added.
+pub fn diff(old: &Setup, new: &Setup) -> SetupDiff {
+    let find_gateway = |setup: &'_ Setup, id: &str| -> Option<Gateway> {
+        setup.gateways.iter().find(|g| g.gateway_id == id).cloned()
+    };
+    let find = |setup: &'_ Setup, url: &str| -> Option<Device> {
+        setup.devices.iter().find(|d| d.device_url == url).cloned()
+    };
+    let mut diff = SetupDiff {
+        devices_removed: old
+            .devices
+            .iter()
+            .filter(|d| find(new, &d.device_url).is_none())
+            .cloned()
+            .collect(),
+        gateways_removed: old
+            .gateways
+            .iter()
+            .filter(|g| find_gateway(new, &g.gateway_id).is_none())
+            .cloned()
+            .collect(),
+        ..SetupDiff::default()
+    };
+
+    for gateway in &new.gateways {
+        match find_gateway(old, &gateway.gateway_id) {
+            None => diff.gateways_added.push(gateway.clone()),
+            Some(before) if before.connectivity != gateway.connectivity => {
+                diff.gateways_changed.push(GatewayChange {
+                    gateway_id: gateway.gateway_id.clone(),
+                    old: before.connectivity,
+                    new: gateway.connectivity.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    for device in &new.devices {
+        let Some(before) = find(old, &device.device_url) else {
+            diff.devices_added.push(device.clone());
+            continue;
+        };
+        if before.label != device.label {
+            diff.devices_renamed.push(Renamed {
+                device_url: device.device_url.clone(),
+                old_label: before.label.clone(),
+                new_label: device.label.clone(),
+            });
+        }
+
+        let states = |d: &Device| {
+            d.states
+                .iter()
+                .map(|s| (s.name.clone(), s.value.clone()))
+                .collect::<Vec<_>>()
+        };
+        diff.states_changed
+            .extend(changes(&device.device_url, states(&before), states(device)));
+
+        let attributes = |d: &Device| {
+            d.attributes
+                .iter()
+                .map(|a| (a.name.clone(), a.value.clone()))
+                .collect::<Vec<_>>()
+        };
+        diff.attributes_changed.extend(changes(
+            &device.device_url,
+            attributes(&before),
+            attributes(device),
+        ));
+    }
+    diff
+}
+
+// Changed and removed values in the order of `old`, followed by added values
+fn changes<T: Clone + PartialEq>(
+    device_url: &DeviceUrl,
+    old: Vec<(String, T)>,
+    new: Vec<(String, T)>,
+) -> Vec<ValueChange<T>> {
+    let change = |name: &str, old: Option<T>, new: Option<T>| ValueChange {
+        device_url: device_url.clone(),
+        name: name.to_string(),
+        old,
+        new,
+    };
+    let lookup = |values: &[(String, T)], name: &str| {
+        values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let mut changes = vec![];
+    for (name, value) in &old {
+        let current = lookup(&new, name);
+        if current.as_ref() != Some(value) {
+            changes.push(change(name, Some(value.clone()), current));
+        }
+    }
+    for (name, value) in &new {
+        if lookup(&old, name).is_none() {
+            changes.push(change(name, None, Some(value.clone())));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(devices: serde_json::Value) -> Setup {
+        setup_with_gateways(serde_json::json!([]), devices)
+    }
+
+    fn setup_with_gateways(gateways: serde_json::Value, devices: serde_json::Value) -> Setup {
+        serde_json::from_value(serde_json::json!({ "gateways": gateways, "devices": devices }))
+            .expect("should parse setup")
+    }
+
+    fn gateway(id: &str, status: &str, version: &str) -> serde_json::Value {
+        serde_json::json!({
+            "gatewayId": id,
+            "connectivity": {"status": status, "protocolVersion": version}
+        })
+    }
+
+    fn device(url: &str, label: &str, closure: i64, firmware: &str) -> serde_json::Value {
+        serde_json::json!({
+            "deviceURL": url, "label": label, "controllableName": "io:Shutter",
+            "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+            "states": [{"name": "core:ClosureState", "type": 1, "value": closure}],
+            "attributes": [{"name": "core:FirmwareRevision", "type": 3, "value": firmware}]
+        })
+    }
+
+    #[test]
+    fn round_trips_and_checks_version() {
+        let snapshot = Snapshot::new(setup(serde_json::json!([device(
+            "io://1/1", "Office", 0, "5.1"
+        )])));
+        let json = snapshot.to_json().expect("should serialize");
+        assert_eq!(Snapshot::from_json(&json).expect("should load"), snapshot);
+
+        let newer = json.replace(r#""version": 1"#, r#""version": 2"#);
+        assert!(matches!(
+            Snapshot::from_json(&newer),
+            Err(SnapshotError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn diffs_devices_states_and_attributes() {
+        let old = setup(serde_json::json!([
+            device("io://1/1", "Office", 0, "5.1"),
+            device("io://1/2", "Hall", 0, "5.1"),
+        ]));
+        let new = setup(serde_json::json!([
+            device("io://1/1", "Study", 100, "5.2"),
+            device("io://1/3", "Kitchen", 0, "5.2"),
+        ]));
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.devices_removed[0].device_url, "io://1/2");
+        assert_eq!(diff.devices_added[0].device_url, "io://1/3");
+        assert_eq!(
+            diff.devices_renamed,
+            vec![Renamed {
+                device_url: "io://1/1".parse().expect("should parse"),
+                old_label: "Office".to_string(),
+                new_label: "Study".to_string(),
+            }]
+        );
+        assert_eq!(diff.states_changed.len(), 1);
+        assert_eq!(diff.states_changed[0].new, Some(DeviceStateValue::Int(100)));
+        assert_eq!(
+            diff.attributes_changed[0].old,
+            Some(DeviceAttributeValue::String("5.1".to_string()))
+        );
+
+        assert!(super::diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn diffs_gateways_by_id() {
+        let old = setup_with_gateways(
+            serde_json::json!([
+                gateway("0000-1111-2222", "OK", "2025.3.2-7"),
+                gateway("3333-4444-5555", "OK", "2025.3.2-7"),
+                gateway("6666-7777-8888", "OK", "2025.3.2-7"),
+            ]),
+            serde_json::json!([]),
+        );
+        let new = setup_with_gateways(
+            serde_json::json!([
+                gateway("6666-7777-8888", "OK", "2025.3.2-7"),
+                gateway("0000-1111-2222", "OK", "2025.4.1-2"),
+                gateway("9999-0000-1111", "OK", "2025.4.1-2"),
+            ]),
+            serde_json::json!([]),
+        );
+
+        let diff = diff(&old, &new);
+        assert_eq!(diff.gateways_removed[0].gateway_id, "3333-4444-5555");
+        assert_eq!(diff.gateways_added[0].gateway_id, "9999-0000-1111");
+        assert_eq!(diff.gateways_changed.len(), 1);
+        assert_eq!(diff.gateways_changed[0].gateway_id, "0000-1111-2222");
+        assert_eq!(diff.gateways_changed[0].old.protocol_version, "2025.3.2-7");
+        assert_eq!(diff.gateways_changed[0].new.protocol_version, "2025.4.1-2");
+
+        let mut offline = new.clone();
+        offline.gateways[0].connectivity.status = "NOT_CONNECTED".to_string();
+        let diff = super::diff(&new, &offline);
+        assert_eq!(diff.gateways_changed[0].gateway_id, "6666-7777-8888");
+        assert_eq!(diff.gateways_changed[0].new.status, "NOT_CONNECTED");
+    }
+}
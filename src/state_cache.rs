@@ -0,0 +1,382 @@
+use crate::api_client::ApiClient;
+use crate::commands::types::{
+    Device, DeviceState, DeviceStateValue, Event, Setup, DEVICE_STATE_CHANGED_EVENT,
+};
+use crate::device_url::DeviceUrl;
+use crate::err::http::RequestError;
+use crate::events::{EventFilter, EventHub};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+const DEVICE_CREATED_EVENT: &str = "DeviceCreatedEvent";
+const DEVICE_REMOVED_EVENT: &str = "DeviceRemovedEvent";
+const CHANGES_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateChange {
+    /// States that changed on a device, with their new values
+    States {
+        device_url: DeviceUrl,
+        states: Vec<DeviceState>,
+    },
+    Added(DeviceUrl),
+    Removed(DeviceUrl),
+    /// All devices were reloaded from the gateway
+    Resynced,
+}
+
+/// Device states kept in memory and updated from gateway events, so reads don't hit the gateway.
+#[derive(Debug, Clone)]
+pub struct StateCache {
+    devices: Arc<RwLock<HashMap<DeviceUrl, Device>>>,
+    changes: broadcast::Sender<StateChange>,
+    revision: watch::Sender<u64>,
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl StateCache {
+    /// A cache holding the devices of `setup`, updated only via `apply_event` and `update`.
+    pub fn from_setup(setup: Setup) -> Self {
+        let (changes, _) = broadcast::channel(CHANGES_CAPACITY);
+        let (revision, _) = watch::channel(0);
+        let cache = Self {
+            devices: Arc::new(RwLock::new(HashMap::new())),
+            changes,
+            revision,
+            task: Arc::new(Mutex::new(None)),
+        };
+        cache.replace(setup);
+        cache
+    }
+
+    /// Seeds the cache from `get_setup`, then follows the events of `hub` and reloads all
+    /// devices every `resync_interval` and whenever the hub renews its listener, to recover from
+    /// missed events.
+    pub async fn start(
+        api: ApiClient,
+        hub: &EventHub,
+        resync_interval: Duration,
+    ) -> Result<Self, RequestError> {
+        // Subscribe before loading the setup so no change gets lost in between
+        let mut events = hub.subscribe(EventFilter {
+            names: [
+                DEVICE_STATE_CHANGED_EVENT,
+                DEVICE_CREATED_EVENT,
+                DEVICE_REMOVED_EVENT,
+            ]
+            .map(str::to_string)
+            .to_vec(),
+            ..EventFilter::default()
+        });
+        let mut renewals = hub.renewals();
+        let cache = Self::from_setup(api.get_setup().await?);
+
+        let task = tokio::spawn({
+            let cache = cache.clone();
+            async move {
+                let mut resync = tokio::time::interval(resync_interval);
+                // The first tick completes immediately and the setup was just loaded
+                resync.tick().await;
+                loop {
+                    tokio::select! {
+                        event = events.recv() => {
+                            let Some(event) = event else { break };
+                            cache.follow(&api, &event).await;
+                        }
+                        _ = resync.tick() => cache.resync(&api).await,
+                        Ok(()) = renewals.changed() => cache.resync(&api).await,
+                    }
+                }
+            }
+        });
+
+        if let Ok(mut current) = cache.task.lock() {
+            *current = Some(task);
+        }
+        Ok(cache)
+    }
+
+    /// Stops following events, the cache keeps its last known states.
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().ok().and_then(|mut t| t.take()) {
+            task.abort();
+        }
+    }
+
+    pub fn device(&self, device_url: &str) -> Option<Device> {
+        self.read(|devices| devices.get(device_url).cloned())
+    }
+
+    pub fn state(&self, device_url: &str, name: &str) -> Option<DeviceStateValue> {
+        self.read(|devices| devices.get(device_url)?.state(name).cloned())
+    }
+
+    pub fn devices(&self) -> Vec<Device> {
+        self.read(|devices| devices.values().cloned().collect())
+    }
+
+    /// Every change applied to the cache. Slow receivers lag and miss changes.
+    pub fn subscribe(&self) -> broadcast::Receiver<StateChange> {
+        self.changes.subscribe()
+    }
+
+    /// A counter that increases with every change, for consumers that only need to know that
+    /// something changed.
+    pub fn watch(&self) -> watch::Receiver<u64> {
+        self.revision.subscribe()
+    }
+
+    /// Applies state changes and removals. Created devices are only known after the next
+    /// `update`, as the event does not carry the device.
+    pub fn apply_event(&self, event: &Event) {
+        let Some(device_url) = event.device_url.clone() else {
+            return;
+        };
+        let change = self.write(|devices| match event.name.as_str() {
+            DEVICE_STATE_CHANGED_EVENT => {
+                let device = devices.get_mut(&device_url)?;
+                for changed in &event.device_states {
+                    match device.states.iter_mut().find(|s| s.name == changed.name) {
+                        Some(state) => state.value = changed.value.clone(),
+                        None => device.states.push(changed.clone()),
+                    }
+                }
+                Some(StateChange::States {
+                    device_url,
+                    states: event.device_states.clone(),
+                })
+            }
+            DEVICE_REMOVED_EVENT => devices
+                .remove(&device_url)
+                .map(|_| StateChange::Removed(device_url)),
+            _ => None,
+        });
+        if let Some(change) = change {
+            self.notify(change);
+        }
+    }
+
+    /// Replaces all devices with the ones from `setup`.
+    pub fn update(&self, setup: Setup) {
+        self.replace(setup);
+        self.notify(StateChange::Resynced);
+    }
+
+    async fn resync(&self, api: &ApiClient) {
+        match api.get_setup().await {
+            Ok(setup) => self.update(setup),
+            Err(e) => warn!("Resyncing device states failed: {e}"),
+        }
+    }
+
+    async fn follow(&self, api: &ApiClient, event: &Event) {
+        match (event.name.as_str(), &event.device_url) {
+            (DEVICE_CREATED_EVENT, Some(device_url)) => match api.get_device(device_url).await {
+                Ok(device) => {
+                    self.write(|devices| devices.insert(device_url.clone(), device));
+                    self.notify(StateChange::Added(device_url.clone()));
+                }
+                Err(e) => warn!("Loading created device {device_url} failed: {e}"),
+            },
+            _ => self.apply_event(event),
+        }
+    }
+
+    fn replace(&self, setup: Setup) {
+        let devices = setup
+            .devices
+            .into_iter()
+            .map(|d| (d.device_url.clone(), d))
+            .collect();
+        self.write(|current| *current = devices);
+    }
+
+    fn notify(&self, change: StateChange) {
+        self.revision.send_modify(|revision| *revision += 1);
+        // Sending only fails if nobody is subscribed
+        let _ = self.changes.send(change);
+    }
+
+    // A panic while holding the lock cannot leave the map half updated, so poisoning is ignored
+    fn read<T>(&self, f: impl FnOnce(&HashMap<DeviceUrl, Device>) -> T) -> T {
+        f(&self.devices.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn write<T>(&self, f: impl FnOnce(&mut HashMap<DeviceUrl, Device>) -> T) -> T {
+        f(&mut self.devices.write().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::traits::HttpMethod::{GET, POST};
+    use crate::transport::{MockTransport, RawResponse};
+    use http::StatusCode;
+
+    fn device(url: &str) -> serde_json::Value {
+        serde_json::json!({
+            "deviceURL": url, "label": url, "controllableName": "io:Shutter",
+            "subsystemId": 0, "type": 1, "available": true, "synced": true, "enabled": true,
+            "states": [{"name": "core:ClosureState", "type": 1, "value": 0}], "attributes": []
+        })
+    }
+
+    fn setup_json(devices: &[&str]) -> serde_json::Value {
+        let devices: Vec<_> = devices.iter().map(|url| device(url)).collect();
+        serde_json::json!({ "gateways": [], "devices": devices })
+    }
+
+    fn setup(devices: &[&str]) -> Setup {
+        serde_json::from_value(setup_json(devices)).expect("should parse setup")
+    }
+
+    // A gateway with `devices` and an event listener `a` returning `events` on every fetch
+    fn gateway(devices: &[&str], events: serde_json::Value) -> MockTransport {
+        MockTransport::new()
+            .respond(
+                GET,
+                "/setup",
+                RawResponse::ok(setup_json(devices).to_string()),
+            )
+            .respond(POST, "/events/register", RawResponse::ok(r#"{"id": "a"}"#))
+            .respond(POST, "/events/a/fetch", RawResponse::ok(events.to_string()))
+            .respond(POST, "/events/a/unregister", RawResponse::ok("[]"))
+    }
+
+    async fn start(mock: &MockTransport) -> (EventHub, StateCache) {
+        let api = ApiClient::with_transport(mock.clone());
+        let hub = EventHub::start(api.clone(), Duration::from_millis(5))
+            .await
+            .expect("should start hub");
+        let cache = StateCache::start(api, &hub, Duration::from_secs(3600))
+            .await
+            .expect("should start cache");
+        (hub, cache)
+    }
+
+    // Waits until `done` holds after a change, failing after a second
+    async fn wait_for(cache: &StateCache, done: impl Fn(&StateCache) -> bool) {
+        let mut revision = cache.watch();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !done(cache) {
+                revision.changed().await.expect("cache should be running");
+            }
+        })
+        .await
+        .expect("cache should reach the expected state")
+    }
+
+    fn event(value: serde_json::Value) -> Event {
+        serde_json::from_value(value).expect("should parse event")
+    }
+
+    #[test]
+    fn applies_state_changes_and_removals() {
+        let cache = StateCache::from_setup(setup(&["io://1/1", "io://1/2"]));
+        let mut changes = cache.subscribe();
+        let revision = cache.watch();
+
+        cache.apply_event(&event(serde_json::json!({
+            "name": "DeviceStateChangedEvent",
+            "deviceURL": "io://1/1",
+            "deviceStates": [{"name": "core:ClosureState", "type": 1, "value": 40}],
+        })));
+        assert_eq!(
+            cache.state("io://1/1", "core:ClosureState"),
+            Some(DeviceStateValue::Int(40))
+        );
+        assert!(matches!(
+            changes.try_recv(),
+            Ok(StateChange::States { device_url, .. }) if device_url == "io://1/1"
+        ));
+
+        cache.apply_event(&event(serde_json::json!({
+            "name": "DeviceRemovedEvent",
+            "deviceURL": "io://1/2",
+        })));
+        assert!(cache.device("io://1/2").is_none());
+        assert_eq!(
+            changes.try_recv(),
+            Ok(StateChange::Removed(
+                "io://1/2".parse().expect("should parse")
+            ))
+        );
+        assert_eq!(*revision.borrow(), 2);
+
+        // Unknown devices are ignored
+        cache.apply_event(&event(serde_json::json!({
+            "name": "DeviceStateChangedEvent",
+            "deviceURL": "io://1/9",
+        })));
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[test]
+    fn update_replaces_devices() {
+        let cache = StateCache::from_setup(setup(&["io://1/1"]));
+        let mut changes = cache.subscribe();
+
+        cache.update(setup(&["io://1/2"]));
+        assert!(cache.device("io://1/1").is_none());
+        assert_eq!(cache.devices().len(), 1);
+        assert_eq!(changes.try_recv(), Ok(StateChange::Resynced));
+    }
+
+    #[tokio::test]
+    async fn start_follows_created_and_removed_devices() {
+        let mock = gateway(
+            &["io://1/1"],
+            serde_json::json!([
+                {"name": "DeviceCreatedEvent", "deviceURL": "io://1/2"},
+                {"name": "DeviceRemovedEvent", "deviceURL": "io://1/1"},
+            ]),
+        )
+        .respond(
+            GET,
+            "/setup/devices/io%3A%2F%2F1%2F2",
+            RawResponse::ok(device("io://1/2").to_string()),
+        );
+        let (hub, cache) = start(&mock).await;
+
+        wait_for(&cache, |c| c.device("io://1/2").is_some()).await;
+        wait_for(&cache, |c| c.device("io://1/1").is_none()).await;
+        assert_eq!(cache.devices().len(), 1);
+
+        cache.stop();
+        hub.stop().await.expect("should stop hub");
+    }
+
+    #[tokio::test]
+    async fn resyncs_after_listener_expiry() {
+        let mock = gateway(&["io://1/1"], serde_json::json!([]));
+        let (hub, cache) = start(&mock).await;
+        assert!(cache.device("io://1/1").is_some());
+
+        // Devices changed while the listener was gone, no event reports it
+        mock.clone()
+            .respond(
+                GET,
+                "/setup",
+                RawResponse::ok(setup_json(&["io://1/3"]).to_string()),
+            )
+            .respond(POST, "/events/register", RawResponse::ok(r#"{"id": "b"}"#))
+            .respond(POST, "/events/b/fetch", RawResponse::ok("[]"))
+            .respond(POST, "/events/b/unregister", RawResponse::ok("[]"))
+            .respond(
+                POST,
+                "/events/a/fetch",
+                RawResponse::new(StatusCode::BAD_REQUEST, ""),
+            );
+
+        wait_for(&cache, |c| c.device("io://1/3").is_some()).await;
+        assert!(cache.device("io://1/1").is_none());
+
+        cache.stop();
+        hub.stop().await.expect("should stop hub");
+    }
+}
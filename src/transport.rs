@@ -0,0 +1,156 @@
+use crate::commands::traits::{HttpMethod, RequestData};
+use crate::err::http::RequestError;
+use http::StatusCode;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const API_BASE_PATH: &str = "/enduser-mobile-web/1/enduserAPI";
+
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<RawResponse, RequestError>> + Send + 'a>>;
+
+/// Sends the requests of an `ApiClient`. Implemented by `ReqwestTransport` for real gateways,
+/// `MockTransport` for tests and the cassette transports.
+pub trait Transport: Debug + Send + Sync {
+    /// Sends `request` and returns the response, whatever its status. Errors are for requests
+    /// that got no response at all.
+    fn send(&self, request: RequestData) -> TransportFuture<'_>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl RawResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self::new(StatusCode::OK, body)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentRequest {
+    pub method: HttpMethod,
+    pub path: String,
+    pub body: Option<String>,
+}
+
+/// Answers requests with canned responses and keeps the requests it got. Clones share their
+/// responses and requests, so a clone can be inspected after handing one to `ApiClient`.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    responses: Vec<(HttpMethod, String, RawResponse)>,
+    requests: Vec<SentRequest>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every `method` request to `path` with `response`, replacing an earlier response
+    /// for the same request. `path` may leave out the `/enduser-mobile-web/1/enduserAPI` prefix.
+    /// Requests without a response get a 404.
+    pub fn respond(self, method: HttpMethod, path: &str, response: RawResponse) -> Self {
+        let path = match path.starts_with(API_BASE_PATH) {
+            true => path.to_string(),
+            false => format!("{API_BASE_PATH}{path}"),
+        };
+        {
+            let mut state = self.lock();
+            state
+                .responses
+                .retain(|(m, p, _)| (m, p) != (&method, &path));
+            state.responses.push((method, path, response));
+        }
+        self
+    }
+
+    /// All requests sent so far, oldest first.
+    pub fn requests(&self) -> Vec<SentRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: RequestData) -> TransportFuture<'_> {
+        let mut state = self.lock();
+        let response = state
+            .responses
+            .iter()
+            .find(|(method, path, _)| method == &request.method && path == &request.path)
+            .map(|(_, _, response)| response.clone())
+            .unwrap_or_else(|| RawResponse::new(StatusCode::NOT_FOUND, ""));
+        state.requests.push(SentRequest {
+            body: Some(request.body.as_bytes())
+                .filter(|b| !b.is_empty())
+                .map(|b| String::from_utf8_lossy(b).into_owned()),
+            method: request.method,
+            path: request.path,
+        });
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::ApiClient;
+
+    #[tokio::test]
+    async fn stubs_gateway_responses() {
+        let mock = MockTransport::new()
+            .respond(
+                HttpMethod::GET,
+                "/apiVersion",
+                RawResponse::ok(r#"{"protocolVersion": "2025.3.2-7"}"#),
+            )
+            .respond(
+                HttpMethod::POST,
+                "/events/register",
+                RawResponse::new(StatusCode::UNAUTHORIZED, ""),
+            );
+        let client = ApiClient::with_transport(mock.clone());
+
+        let version = client.get_version().await.expect("should get version");
+        assert_eq!(version.protocol_version, "2025.3.2-7");
+        assert!(matches!(
+            client.register_event_listener().await,
+            Err(RequestError::Auth { .. })
+        ));
+        assert!(matches!(
+            client.get_setup().await,
+            Err(RequestError::Status {
+                status: StatusCode::NOT_FOUND,
+                ..
+            })
+        ));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[0].path,
+            "/enduser-mobile-web/1/enduserAPI/apiVersion"
+        );
+        assert_eq!(requests[1].method, HttpMethod::POST);
+    }
+}
@@ -1,43 +1,47 @@
-#[cfg(test)]
+// Runs against the in-process simulator, `cargo test --features test-util --test http_tests`
+#[cfg(all(test, feature = "test-util"))]
 mod http_integration_tests {
     use rstest::*;
-    use somfy_sdk::api_client::{ApiClient, ApiClientConfig, CertificateHandling, HttpProtocol};
+    use somfy_sdk::api_client::ApiClient;
     use somfy_sdk::commands::types::DeviceStateValue::{Boolean, Int, String};
+    use somfy_sdk::device_url::DeviceUrl;
+    use somfy_sdk::simulator::Simulator;
     use std::time::Duration;
 
+    const SHUTTER: &str = "io://0000-1111-2222/1";
+
+    fn url(device_url: &str) -> DeviceUrl {
+        device_url.parse().expect("should parse device url")
+    }
+
+    // The simulator stops when dropped, so it is returned alongside the client
     #[fixture]
-    async fn api_client_localhost() -> ApiClient {
-        ApiClient::new(ApiClientConfig {
-            protocol: HttpProtocol::HTTP,
-            port: 3000,
-            url: "localhost".to_string(),
-            api_key: "my_key".to_string(),
-            cert_handling: CertificateHandling::CertProvided(
-                "./tests/fixtures/cert/overkiz-root-ca-2048.crt".to_string(),
-            ),
-        })
-        .await
-        .expect("should create an ApiClient")
+    async fn api_client_simulator() -> (Simulator, ApiClient) {
+        let simulator = Simulator::start().await.expect("should start simulator");
+        let client = ApiClient::new(simulator.client_config())
+            .await
+            .expect("should create an ApiClient");
+        (simulator, client)
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_version() {
-        let res = api_client_localhost()
-            .await
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
             .get_version()
             .await
             .expect("should get a correct response from the getVersion endpoint");
-        assert_eq!(res.protocol_version, "2022.1.3-1".to_string())
+        assert_eq!(res.protocol_version, "2025.3.2-7".to_string())
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_gateways() {
-        let res = api_client_localhost()
-            .await
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
             .get_gateways()
             .await
             .expect("should get a correct response from get gateways");
@@ -50,103 +54,101 @@ mod http_integration_tests {
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_setup() {
-        let res = api_client_localhost()
-            .await
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
             .get_setup()
             .await
             .expect("should get a correct response from get setup");
-        assert_eq!(res.devices.len(), 2);
+        assert_eq!(res.devices.len(), 3);
         assert_eq!(res.gateways.len(), 1);
         assert_eq!(res.gateways[0].gateway_id, "0000-1111-2222");
-        assert_eq!(res.devices[0].device_url, "io://0812-2424-9999/246132")
+        assert_eq!(res.devices[0].device_url, SHUTTER)
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_devices() {
-        let res = api_client_localhost()
-            .await
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
             .get_devices()
             .await
             .expect("should get a correct response from get devices");
-        assert_eq!(res.len(), 2);
-        assert_eq!(res[0].device_url, "io://0812-2424-9999/246132")
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].device_url, SHUTTER)
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_device() {
-        let res = api_client_localhost()
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
+            .get_device(&url(SHUTTER))
             .await
-            .get_device("d123")
-            .await
-            .expect("should get a correct response from get devices");
-        assert_eq!(res.device_url, "io://0812-2424-9999/246132");
-        assert_eq!(res.controllable_name, "io:ExteriorVenetianBlindIOComponent")
+            .expect("should get a correct response from get device");
+        assert_eq!(res.device_url, SHUTTER);
+        assert_eq!(res.controllable_name, "io:RollerShutterGenericIOComponent")
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_device_states() {
-        let res = api_client_localhost()
-            .await
-            .get_device_states("d123")
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
+            .get_device_states(&url(SHUTTER))
             .await
             .expect("should get a correct response from get device states");
-        assert_eq!(res.len(), 2);
-        assert_eq!(res[0].name, "core:StatusState");
-        assert_eq!(res[0].value, String("available".to_string()));
-        assert_eq!(res[1].value, Boolean(false))
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].name, "core:ClosureState");
+        assert_eq!(res[0].value, Int(0));
+        assert_eq!(res[2].value, Boolean(false))
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_device_state() {
-        let res = api_client_localhost()
-            .await
-            .get_device_state("d123", "s123")
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
+            .get_device_state(&url(SHUTTER), "core:OpenClosedState")
             .await
             .expect("should get a correct response from get device state");
-        assert_eq!(res.name, "core:StatusState");
-        assert_eq!(res.value, Int(5))
+        assert_eq!(res.name, "core:OpenClosedState");
+        assert_eq!(res.value, String("open".to_string()))
     }
 
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     async fn http_get_devices_by_controllable() {
-        let res = api_client_localhost()
+        let (_simulator, client) = api_client_simulator().await;
+        let res = client
+            .get_devices_by_controllable("io:RollerShutterGenericIOComponent")
             .await
-            .get_devices_by_controllable("c123")
-            .await
-            .expect("should get a correct response from get device states");
-        assert_eq!(res.len(), 2);
-        assert_eq!(res[0], "io://0812-2424-9999/12936651")
+            .expect("should get a correct response from get devices by controllable");
+        assert_eq!(res, vec![SHUTTER])
     }
 
-    #[ignore] // Ignored until we find a better alternative to json-server
     #[rstest]
     #[tokio::test]
     #[timeout(Duration::from_millis(1000))]
     #[cfg(feature = "generic-exec")]
     async fn http_post_exec_actions() {
+        let (_simulator, client) = api_client_simulator().await;
         let ag = somfy_sdk::commands::types::ActionGroup {
             label: Some("Some Test".to_string()),
             actions: vec![somfy_sdk::commands::types::Action {
-                device_url: "mock-device".to_string(),
+                device_url: url(SHUTTER),
                 commands: vec![],
             }],
         };
 
-        let res = api_client_localhost()
-            .await
+        let res = client
             .execute_actions(&ag)
             .await
-            .expect("should get a correct response from get device states");
-        assert_eq!(res.exec_id, "exec-12345678-1234-5678-9012-123456789012");
+            .expect("should get a correct response from execute actions");
+        assert!(!res.exec_id.is_empty());
     }
 }
@@ -0,0 +1,57 @@
+// Runs against the in-process simulator and requires an MQTT broker on port 1883, e.g.
+// `mosquitto -p 1883` and `cargo test --features mqtt,test-util --test mqtt_tests -- --ignored`
+#![cfg(all(feature = "mqtt", feature = "test-util"))]
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use somfy_sdk::api_client::ApiClient;
+use somfy_sdk::mqtt::{run, BridgeConfig};
+use somfy_sdk::simulator::Simulator;
+use std::time::Duration;
+
+#[tokio::test]
+#[ignore = "requires an MQTT broker on localhost:1883"]
+async fn publishes_device_availability() {
+    let prefix = format!("somfy-test-{}", std::process::id());
+    let config = BridgeConfig {
+        prefix: prefix.clone(),
+        ..BridgeConfig::default()
+    };
+    let simulator = Simulator::start().await.expect("should start simulator");
+    let api_config = simulator.client_config();
+    let bridge = tokio::spawn(async move {
+        let api = ApiClient::new(api_config)
+            .await
+            .expect("should create an ApiClient");
+        run(
+            &api,
+            MqttOptions::new("somfy-bridge-test", "localhost", 1883),
+            config,
+        )
+        .await
+    });
+
+    let (client, mut event_loop) = AsyncClient::new(
+        MqttOptions::new("somfy-observer-test", "localhost", 1883),
+        16,
+    );
+    client
+        .subscribe(format!("{prefix}/#"), QoS::AtLeastOnce)
+        .await
+        .expect("should subscribe");
+
+    let expected_topic = format!("{prefix}/Living_Room/availability");
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(Event::Incoming(Packet::Publish(publish))) = event_loop.poll().await {
+                if publish.topic == expected_topic {
+                    return publish;
+                }
+            }
+        }
+    })
+    .await
+    .expect("should receive availability of Living Room");
+
+    bridge.abort();
+    assert_eq!(received.payload.as_ref(), b"online");
+}
@@ -0,0 +1,328 @@
+// Runs against the in-process simulator, `cargo test --features test-util,generic-exec --test simulator_tests`
+#![cfg(feature = "test-util")]
+
+use reqwest::StatusCode;
+use somfy_sdk::api_client::ApiClient;
+use somfy_sdk::cassette::Cassette;
+use somfy_sdk::commands::types::DeviceStateValue::{Int, String};
+use somfy_sdk::device_url::DeviceUrl;
+use somfy_sdk::err::http::RequestError;
+use somfy_sdk::simulator::{Simulator, SimulatorConfig};
+
+const SHUTTER: &str = "io://0000-1111-2222/1";
+
+fn url(device_url: &str) -> DeviceUrl {
+    device_url.parse().expect("should parse device url")
+}
+
+async fn start() -> (Simulator, ApiClient) {
+    let simulator = Simulator::start().await.expect("should start simulator");
+    let client = ApiClient::new(simulator.client_config())
+        .await
+        .expect("should create an ApiClient");
+    (simulator, client)
+}
+
+#[tokio::test]
+async fn serves_setup_and_devices() {
+    let (_simulator, client) = start().await;
+
+    assert!(!client
+        .get_version()
+        .await
+        .expect("should get version")
+        .protocol_version
+        .is_empty());
+    assert_eq!(
+        client.get_gateways().await.expect("should get gateways")[0].gateway_id,
+        "0000-1111-2222"
+    );
+    assert_eq!(
+        client
+            .get_setup()
+            .await
+            .expect("should get setup")
+            .devices
+            .len(),
+        3
+    );
+    assert_eq!(
+        client
+            .get_devices()
+            .await
+            .expect("should get devices")
+            .len(),
+        3
+    );
+
+    let device = client
+        .get_device(&url(SHUTTER))
+        .await
+        .expect("should get device");
+    assert_eq!(device.label, "Living Room");
+    let states = client
+        .get_device_states(&url(SHUTTER))
+        .await
+        .expect("should get states");
+    assert_eq!(states[0].value, Int(0));
+    let state = client
+        .get_device_state(&url(SHUTTER), "core:OpenClosedState")
+        .await
+        .expect("should get state");
+    assert_eq!(state.value, String("open".to_string()));
+    let urls = client
+        .get_devices_by_controllable("io:DimmableLightIOComponent")
+        .await
+        .expect("should get devices by controllable");
+    assert_eq!(urls, vec!["io://0000-1111-2222/3"]);
+
+    assert!(matches!(
+        client.get_device(&url("io://0000-1111-2222/9")).await,
+        Err(RequestError::Status {
+            status: StatusCode::NOT_FOUND,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn rejects_wrong_api_key() {
+    let simulator = Simulator::with_config(SimulatorConfig {
+        api_key: "secret".to_string(),
+        ..SimulatorConfig::default()
+    })
+    .await
+    .expect("should start simulator");
+    let mut config = simulator.client_config();
+    config.api_key = "wrong".to_string();
+    let client = ApiClient::new(config)
+        .await
+        .expect("should create an ApiClient");
+
+    assert!(matches!(
+        client.get_setup().await,
+        Err(RequestError::Auth { .. })
+    ));
+}
+
+#[tokio::test]
+async fn injects_failures() {
+    let (simulator, client) = start().await;
+    simulator.fail_requests("/setup", StatusCode::SERVICE_UNAVAILABLE, 1);
+
+    assert!(matches!(
+        client.get_setup().await,
+        Err(RequestError::Status {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            ..
+        })
+    ));
+    assert!(client.get_setup().await.is_ok());
+}
+
+#[tokio::test]
+async fn queues_events_per_listener() {
+    let (simulator, client) = start().await;
+    let first = client
+        .register_event_listener()
+        .await
+        .expect("should register")
+        .id;
+    let second = client
+        .register_event_listener()
+        .await
+        .expect("should register")
+        .id;
+
+    simulator.set_available(SHUTTER, false);
+    assert_eq!(
+        client
+            .fetch_events(&first)
+            .await
+            .expect("should fetch")
+            .len(),
+        1
+    );
+    assert!(client
+        .fetch_events(&first)
+        .await
+        .expect("should fetch")
+        .is_empty());
+    assert_eq!(
+        client
+            .fetch_events(&second)
+            .await
+            .expect("should fetch")
+            .len(),
+        1
+    );
+
+    client
+        .unregister_event_listener(&first)
+        .await
+        .expect("should unregister");
+    assert!(client.fetch_events(&first).await.is_err());
+}
+
+#[tokio::test]
+async fn records_and_replays_cassettes() {
+    let simulator = Simulator::start().await.expect("should start simulator");
+    let path = std::env::temp_dir().join(format!("somfy_sdk_{}_cassette.json", std::process::id()));
+    let recording = ApiClient::recording(simulator.client_config(), &path)
+        .await
+        .expect("should create a recording ApiClient");
+    let recorded = recording
+        .get_device(&url(SHUTTER))
+        .await
+        .expect("should get device");
+    assert!(recording
+        .get_device(&url("io://0000-1111-2222/9"))
+        .await
+        .is_err());
+    drop(simulator);
+
+    let cassette = std::fs::read_to_string(&path).expect("should write cassette");
+    assert!(
+        !cassette.contains("simulator"),
+        "api key should be redacted"
+    );
+
+    let replaying = ApiClient::replaying(Cassette::load(&path).expect("should load cassette"));
+    assert_eq!(
+        replaying
+            .get_device(&url(SHUTTER))
+            .await
+            .expect("should replay"),
+        recorded
+    );
+    assert!(matches!(
+        replaying.get_device(&url("io://0000-1111-2222/9")).await,
+        Err(RequestError::Status {
+            status: StatusCode::NOT_FOUND,
+            ..
+        })
+    ));
+    // Every recorded response is served once
+    assert!(replaying.get_device(&url(SHUTTER)).await.is_err());
+    std::fs::remove_file(&path).expect("should clean up");
+}
+
+#[cfg(feature = "generic-exec")]
+mod executions {
+    use super::*;
+    use somfy_sdk::commands::types::{Action, ActionGroup, Command};
+    use std::time::Duration;
+
+    fn close_shutter() -> ActionGroup {
+        ActionGroup {
+            label: Some("Close".to_string()),
+            actions: vec![Action {
+                device_url: url(SHUTTER),
+                commands: vec![Command {
+                    name: "close".to_string(),
+                    parameters: vec![],
+                }],
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn executions_move_devices_until_completed() {
+        let (_simulator, client) = start().await;
+        let listener = client
+            .register_event_listener()
+            .await
+            .expect("should register")
+            .id;
+
+        let exec_id = client
+            .execute_actions(&close_shutter())
+            .await
+            .expect("should execute")
+            .exec_id;
+        let execution = client
+            .get_execution(&exec_id)
+            .await
+            .expect("should get execution");
+        assert_eq!(execution.state, "IN_PROGRESS");
+
+        let mut events = vec![];
+        let completed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let fetched = client.fetch_events(&listener).await.expect("should fetch");
+                events.extend(fetched);
+                if events
+                    .iter()
+                    .any(|e| e.new_state.as_deref() == Some("COMPLETED"))
+                {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        assert!(completed.is_ok(), "execution should complete");
+
+        // The shutter passes intermediate positions before it is closed
+        let closures: Vec<_> = events
+            .iter()
+            .flat_map(|e| &e.device_states)
+            .filter(|s| s.name == "core:ClosureState")
+            .map(|s| s.value.clone())
+            .collect();
+        assert!(closures.len() > 1);
+        assert_eq!(closures.last(), Some(&Int(100)));
+        assert!(client
+            .get_current_executions()
+            .await
+            .expect("should list")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancels_executions() {
+        let (_simulator, client) = start().await;
+        let exec_id = client
+            .execute_actions(&close_shutter())
+            .await
+            .expect("should execute")
+            .exec_id;
+        assert_eq!(
+            client
+                .get_current_executions()
+                .await
+                .expect("should list")
+                .len(),
+            1
+        );
+
+        client
+            .cancel_execution(&exec_id)
+            .await
+            .expect("should cancel");
+        assert!(client
+            .get_current_executions()
+            .await
+            .expect("should list")
+            .is_empty());
+        client
+            .cancel_all_executions()
+            .await
+            .expect("should cancel all");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_devices() {
+        let (_simulator, client) = start().await;
+        let mut action_group = close_shutter();
+        action_group.actions[0].device_url = url("io://0000-1111-2222/9");
+
+        assert!(matches!(
+            client.execute_actions(&action_group).await,
+            Err(RequestError::Status {
+                status: StatusCode::BAD_REQUEST,
+                ..
+            })
+        ));
+    }
+}